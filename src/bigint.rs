@@ -0,0 +1,360 @@
+use core::{cmp::Ordering, convert::Infallible, f64::consts::LOG10_2, fmt};
+
+/// Integer of any size, used once a value doesn't fit in `u64` or `i64`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    /// Base 2^32 digits, least significant first, without trailing zeros
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        // NOTE: There is no negative zero
+        let negative = negative && !magnitude.is_empty();
+        Self {
+            negative,
+            magnitude,
+        }
+    }
+
+    /// Parses decimal digits with an optional leading `-`
+    pub fn parse(str: &str) -> Option<Self> {
+        Self::parse_radix(str, 10)
+    }
+
+    /// Parses digits in the given base with an optional leading `-`
+    pub fn parse_radix(str: &str, radix: u32) -> Option<Self> {
+        let (negative, digits) = match str.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, str),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut magnitude = vec![];
+        for digit in digits.chars() {
+            mul_small(&mut magnitude, radix, digit.to_digit(radix)?);
+        }
+        Some(Self::new(negative, magnitude))
+    }
+
+    /// Exact value of a finite float without a fractional part
+    pub fn from_integral_f64(value: f64) -> Self {
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        // NOTE: Subnormals have no implicit leading bit
+        let (mantissa, exponent) = match exponent {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, exponent - 1075),
+        };
+
+        let magnitude = if exponent >= 0 {
+            Self::from(mantissa as i128).mul(&Self::from(2).pow(exponent as u64))
+        } else {
+            Self::from(mantissa.checked_shr(-exponent as u32).unwrap_or(0) as i128)
+        };
+        if value < 0.0 {
+            magnitude.neg()
+        } else {
+            magnitude
+        }
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.magnitude.len() > 4 {
+            return None;
+        }
+        let mut value: u128 = 0;
+        for &digit in self.magnitude.iter().rev() {
+            value = (value << 32) | digit as u128;
+        }
+        if self.negative {
+            0_i128.checked_sub_unsigned(value)
+        } else {
+            i128::try_from(value).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // NOTE: Going through the decimal form rounds correctly
+        self.to_string().parse().unwrap()
+    }
+
+    fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(
+                self.negative,
+                add_magnitudes(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Self::new(
+                other.negative,
+                sub_magnitudes(&other.magnitude, &self.magnitude),
+            ),
+            _ => Self::new(
+                self.negative,
+                sub_magnitudes(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut result = vec![0_u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, &b) in other.magnitude.iter().enumerate() {
+                let current = result[i + j] as u64 + a as u64 * b as u64 + carry;
+                result[i + j] = current as u32;
+                carry = current >> 32;
+            }
+            result[i + other.magnitude.len()] = carry as u32;
+        }
+        Self::new(self.negative != other.negative, result)
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.magnitude.clone())
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(false, self.magnitude.clone())
+    }
+
+    /// Square and multiply
+    pub fn pow(&self, exponent: u64) -> Self {
+        match self.try_pow(exponent, || Ok::<_, Infallible>(())) {
+            Ok(result) => result,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Same as `pow` but calls `check` before every multiplication, so a long
+    /// computation can be stopped
+    pub fn try_pow<E>(
+        &self,
+        mut exponent: u64,
+        mut check: impl FnMut() -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let mut result = Self::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                check()?;
+                result = result.mul(&base);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                check()?;
+                base = base.mul(&base);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Number of bits of the magnitude, 0 for zero
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(top) => self.magnitude.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Number of decimal digits, can be one too many
+    pub fn digits(&self) -> usize {
+        (self.bits() as f64 * LOG10_2) as usize + 1
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(value: i128) -> Self {
+        let mut magnitude = vec![];
+        let mut rest = value.unsigned_abs();
+        while rest > 0 {
+            magnitude.push(rest as u32);
+            rest >>= 32;
+        }
+        Self::new(value < 0, magnitude)
+    }
+}
+
+/// `magnitude * factor + addend`, in place
+fn mul_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in magnitude.iter_mut() {
+        let current = *digit as u64 * factor as u64 + carry;
+        *digit = current as u32;
+        carry = current >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides in place and returns the remainder
+fn div_small(magnitude: &mut [u32], divisor: u32) -> u32 {
+    let mut remainder = 0_u64;
+    for digit in magnitude.iter_mut().rev() {
+        let current = (remainder << 32) | *digit as u64;
+        *digit = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    remainder as u32
+}
+
+fn cmp_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0_u64;
+    for (i, &digit) in long.iter().enumerate() {
+        let current = digit as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        result.push(current as u32);
+        carry = current >> 32;
+    }
+    result.push(carry as u32);
+    result
+}
+
+/// `a - b`, where `a` is at least as large as `b`
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0_i64;
+    for (i, &digit) in a.iter().enumerate() {
+        let mut current = digit as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        borrow = (current < 0) as i64;
+        if current < 0 {
+            current += 1 << 32;
+        }
+        result.push(current as u32);
+    }
+    result
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitudes(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitudes(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // NOTE: Peel off 9 decimal digits at a time, least significant first
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = vec![];
+        while magnitude.iter().any(|&digit| digit != 0) {
+            chunks.push(div_small(&mut magnitude, 1_000_000_000));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(str: &str) -> BigInt {
+        BigInt::parse(str).unwrap()
+    }
+
+    #[test]
+    fn parsing_and_printing() {
+        for str in [
+            "0",
+            "-1",
+            "18446744073709551616",
+            "-340282366920938463463374607431768211457",
+            "1000000000000000000000000000000",
+        ] {
+            assert_eq!(str, big(str).to_string());
+        }
+        assert_eq!("0", big("-0").to_string());
+        assert_eq!(None, BigInt::parse("12a"));
+        assert_eq!(None, BigInt::parse("-"));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+
+        assert_eq!("-864197532086419753208641975320", a.add(&b).to_string());
+        assert_eq!("1111111110111111111011111111100", a.sub(&b).to_string());
+        assert_eq!(
+            "-121932631137021795226185032733622923332237463801111263526900",
+            a.mul(&b).to_string()
+        );
+        assert_eq!("0", a.sub(&a).to_string());
+        assert_eq!(Some(-5), BigInt::from(-5_i128).to_i128());
+        assert_eq!(
+            None,
+            big("340282366920938463463374607431768211456").to_i128()
+        );
+        assert!(b < a);
+        assert_eq!(1.2345678901234568e29, a.to_f64());
+        assert_eq!("987654321098765432109876543210", b.abs().to_string());
+        assert_eq!(
+            "-2535301200456458802993406410752",
+            big("-2").pow(101).to_string()
+        );
+        assert_eq!("1", a.pow(0).to_string());
+        assert_eq!(
+            "-1180591620717411303424",
+            BigInt::from_integral_f64(-1180591620717411303424.0).to_string()
+        );
+        assert_eq!("0", BigInt::from_integral_f64(0.0).to_string());
+        assert_eq!("3", BigInt::from_integral_f64(3.0).to_string());
+        assert_eq!(Err("stopped"), big("3").try_pow(1000, || Err("stopped")));
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(0, big("0").bits());
+        assert_eq!(65, big("-18446744073709551616").bits());
+        assert_eq!(1, big("0").digits());
+        assert_eq!(3, big("255").digits());
+        assert_eq!(20, big("18446744073709551616").digits());
+    }
+}
@@ -1,131 +1,1258 @@
-use crate::{interpreter::Interpreter, lexer::ILToken};
+use std::{cmp::Ordering, f64::consts::LOG10_2, io::Read, str::FromStr};
 
-pub fn word_drop(interpreter: &mut Interpreter) {
-    interpreter.pop_value();
+use crate::{
+    bigint::BigInt,
+    capabilities::Capability,
+    errors::{InputError, RuntimeError},
+    interpreter::{BuiltInAction, Interpreter, Quotation, StackValue},
+    lexer::{ArgumentType, ILToken},
+};
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub action: BuiltInAction,
+    /// Stack effect, lowercase single letters stand for a value of any type
+    pub signature: &'static str,
+    pub description: &'static str,
+    /// What the word needs access to on the host, if anything
+    pub capability: Option<Capability>,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "drop",
+        action: word_drop,
+        signature: "( a -- )",
+        description: "Removes the top value of the stack",
+        capability: None,
+    },
+    Builtin {
+        name: "dup",
+        action: word_dup,
+        signature: "( a -- a a )",
+        description: "Duplicates the top value of the stack",
+        capability: None,
+    },
+    Builtin {
+        name: "swap",
+        action: word_swap,
+        signature: "( a b -- b a )",
+        description: "Swaps the top two values of the stack",
+        capability: None,
+    },
+    Builtin {
+        name: "exit",
+        action: word_exit,
+        signature: "( number -- )",
+        description: "Stops the program with the given exit code",
+        capability: None,
+    },
+    Builtin {
+        name: "print",
+        action: word_print,
+        signature: "( a -- )",
+        description: "Prints the top value of the stack followed by a newline",
+        capability: Some(Capability::Stdout),
+    },
+    Builtin {
+        name: "get_line",
+        action: word_get_line,
+        signature: "( -- string )",
+        description: "Reads a line from the input without surrounding whitespace",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "+",
+        action: word_add,
+        signature: "( number number -- number )",
+        description: "Adds two numbers",
+        capability: None,
+    },
+    Builtin {
+        name: "-",
+        action: word_subtract,
+        signature: "( number number -- number )",
+        description: "Subtracts the top number from the one below it",
+        capability: None,
+    },
+    Builtin {
+        name: "/",
+        action: word_divide,
+        signature: "( number number -- number )",
+        description: "Divides the second number by the top number",
+        capability: None,
+    },
+    Builtin {
+        name: "*",
+        action: word_multiply,
+        signature: "( number number -- number )",
+        description: "Multiplies two numbers",
+        capability: None,
+    },
+    Builtin {
+        name: "<",
+        action: word_less,
+        signature: "( a a -- bool )",
+        description: "Whether the second value is less than the top value",
+        capability: None,
+    },
+    Builtin {
+        name: ">",
+        action: word_more,
+        signature: "( a a -- bool )",
+        description: "Whether the second value is more than the top value",
+        capability: None,
+    },
+    Builtin {
+        name: "<=",
+        action: word_less_or_equal,
+        signature: "( a a -- bool )",
+        description: "Whether the second value is less than or equal to the top value",
+        capability: None,
+    },
+    Builtin {
+        name: ">=",
+        action: word_more_or_equal,
+        signature: "( a a -- bool )",
+        description: "Whether the second value is more than or equal to the top value",
+        capability: None,
+    },
+    Builtin {
+        name: "==",
+        action: word_equal,
+        signature: "( a a -- bool )",
+        description: "Whether two values are equal",
+        capability: None,
+    },
+    Builtin {
+        name: "!=",
+        action: word_not_equal,
+        signature: "( a a -- bool )",
+        description: "Whether two values are not equal",
+        capability: None,
+    },
+    Builtin {
+        name: "and",
+        action: word_and,
+        signature: "( a a -- a )",
+        description: "Logical and of two bools, or bitwise and of two integers",
+        capability: None,
+    },
+    Builtin {
+        name: "or",
+        action: word_or,
+        signature: "( a a -- a )",
+        description: "Logical or of two bools, or bitwise or of two integers",
+        capability: None,
+    },
+    Builtin {
+        name: "xor",
+        action: word_xor,
+        signature: "( a a -- a )",
+        description: "Whether exactly one of two bools is true, or bitwise xor of two integers",
+        capability: None,
+    },
+    Builtin {
+        name: "not",
+        action: word_not,
+        signature: "( a -- a )",
+        description: "Logical not of a bool, or bitwise not of an integer",
+        capability: None,
+    },
+    Builtin {
+        name: "shl",
+        action: word_shift_left,
+        signature: "( number number -- number )",
+        description: "Shifts the second integer left by the top one, between 0 and 63",
+        capability: None,
+    },
+    Builtin {
+        name: "shr",
+        action: word_shift_right,
+        signature: "( number number -- number )",
+        description: "Shifts the second integer right by the top one, rounding toward negative infinity",
+        capability: None,
+    },
+    Builtin {
+        name: "mod",
+        action: word_mod,
+        signature: "( number number -- number )",
+        description: "Remainder of dividing the second number by the top number, with the sign of the second",
+        capability: None,
+    },
+    Builtin {
+        name: "divmod",
+        action: word_divmod,
+        signature: "( number number -- number number )",
+        description: "Quotient rounded toward zero and remainder of dividing the second number by the top number",
+        capability: None,
+    },
+    Builtin {
+        name: "abs",
+        action: word_abs,
+        signature: "( number -- number )",
+        description: "Absolute value of a number",
+        capability: None,
+    },
+    Builtin {
+        name: "neg",
+        action: word_neg,
+        signature: "( number -- number )",
+        description: "Negates a number",
+        capability: None,
+    },
+    Builtin {
+        name: "min",
+        action: word_min,
+        signature: "( number number -- number )",
+        description: "Smaller of two numbers",
+        capability: None,
+    },
+    Builtin {
+        name: "max",
+        action: word_max,
+        signature: "( number number -- number )",
+        description: "Larger of two numbers",
+        capability: None,
+    },
+    Builtin {
+        name: "pow",
+        action: word_pow,
+        signature: "( number number -- number )",
+        description: "Raises the second number to the power of the top number, exactly for integers",
+        capability: None,
+    },
+    Builtin {
+        name: "sqrt",
+        action: word_sqrt,
+        signature: "( number -- number )",
+        description: "Square root of a number as a float",
+        capability: None,
+    },
+    Builtin {
+        name: "to_int",
+        action: word_to_int,
+        signature: "( a -- number )",
+        description: "Converts a number, string or bool to a signed integer, floats are truncated",
+        capability: None,
+    },
+    Builtin {
+        name: "to_uint",
+        action: word_to_uint,
+        signature: "( a -- number )",
+        description: "Converts a number, string or bool to an unsigned integer, floats are truncated",
+        capability: None,
+    },
+    Builtin {
+        name: "to_float",
+        action: word_to_float,
+        signature: "( a -- number )",
+        description: "Converts a number, string or bool to a floating point number",
+        capability: None,
+    },
+    Builtin {
+        name: "to_string",
+        action: word_to_string,
+        signature: "( a -- string )",
+        description: "Converts any value to a string the same way `print` shows it",
+        capability: None,
+    },
+    Builtin {
+        name: "to_bool",
+        action: word_to_bool,
+        signature: "( a -- bool )",
+        description: "Whether the value counts as true in an `if`",
+        capability: None,
+    },
+    Builtin {
+        name: "get_int",
+        action: word_get_int,
+        signature: "( -- number )",
+        description: "Reads a line from the input as a signed integer",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "get_uint",
+        action: word_get_uint,
+        signature: "( -- number )",
+        description: "Reads a line from the input as an unsigned integer",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "get_float",
+        action: word_get_float,
+        signature: "( -- number )",
+        description: "Reads a line from the input as a floating point number",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "read_all",
+        action: word_read_all,
+        signature: "( -- string )",
+        description: "Reads everything left in the input",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "eof?",
+        action: word_eof,
+        signature: "( -- bool )",
+        description: "Whether everything in the input was read",
+        capability: Some(Capability::Stdin),
+    },
+    Builtin {
+        name: "assert",
+        action: word_assert,
+        signature: "( a -- )",
+        description: "Fails the running test or program if the top value is false",
+        capability: None,
+    },
+    Builtin {
+        name: "assert_eq",
+        action: word_assert_eq,
+        signature: "( a b -- )",
+        description: "Fails the running test or program if the top two values are not equal",
+        capability: None,
+    },
+    Builtin {
+        name: "call",
+        action: word_call,
+        signature: "( quotation -- )",
+        description: "Runs the quotation",
+        capability: None,
+    },
+    Builtin {
+        name: "map",
+        action: word_map,
+        signature: "( list quotation -- list )",
+        description: "Runs the quotation on every item, collecting the results into a new list",
+        capability: None,
+    },
+    Builtin {
+        name: "filter",
+        action: word_filter,
+        signature: "( list quotation -- list )",
+        description: "Keeps the items for which the quotation leaves true",
+        capability: None,
+    },
+    Builtin {
+        name: "fold",
+        action: word_fold,
+        signature: "( list a quotation -- b )",
+        description: "Combines the items from the first to the last, starting with the value below the quotation",
+        capability: None,
+    },
+    Builtin {
+        name: "each",
+        action: word_each,
+        signature: "( list quotation -- )",
+        description: "Runs the quotation on every item",
+        capability: None,
+    },
+    Builtin {
+        name: "times",
+        action: word_times,
+        signature: "( number quotation -- )",
+        description: "Runs the quotation the given number of times",
+        capability: None,
+    },
+    Builtin {
+        name: "read_file",
+        action: word_read_file,
+        signature: "( string -- string )",
+        description: "Reads the whole file at the given path",
+        capability: Some(Capability::FsRead),
+    },
+    Builtin {
+        name: "write_file",
+        action: word_write_file,
+        signature: "( string string -- )",
+        description: "Writes the string below the top to the file at the path on top",
+        capability: Some(Capability::FsWrite),
+    },
+    Builtin {
+        name: "env",
+        action: word_env,
+        signature: "( string -- string )",
+        description: "Value of an environment variable, empty if it is not set",
+        capability: Some(Capability::Env),
+    },
+    Builtin {
+        name: "now",
+        action: word_now,
+        signature: "( -- number )",
+        description: "Seconds since the Unix epoch",
+        capability: Some(Capability::Clock),
+    },
+    Builtin {
+        name: "random",
+        action: word_random,
+        signature: "( -- number )",
+        description: "Random number between 0 (inclusive) and 1 (exclusive)",
+        capability: Some(Capability::Random),
+    },
+];
+
+pub fn find_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub fn word_drop(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    // NOTE: Dropping from an empty stack is allowed
+    let _ = interpreter.pop_value();
+
+    Ok(())
+}
+
+/// Value of an integer that fits in 64 bits
+fn small_integer(value: &StackValue) -> Option<i128> {
+    match value {
+        StackValue::UnsignedInt(int) => Some(*int as i128),
+        StackValue::SignedInt(int) => Some(*int as i128),
+        _ => None,
+    }
+}
+
+fn big_integer(value: &StackValue) -> Option<BigInt> {
+    match value {
+        StackValue::BigInt(int) => Some(int.clone()),
+        value => small_integer(value).map(BigInt::from),
+    }
+}
+
+/// Exact result of an operation on two integers, promoted to a big integer
+/// when it doesn't fit in 64 bits. `None` if either value is not an integer
+fn integer_arithmetic(
+    a: &StackValue,
+    b: &StackValue,
+    small: fn(i128, i128) -> Option<i128>,
+    big: fn(&BigInt, &BigInt) -> BigInt,
+) -> Option<StackValue> {
+    if let (Some(a), Some(b)) = (small_integer(a), small_integer(b)) {
+        if let Some(result) = small(a, b) {
+            return Some(result.into());
+        }
+    }
+
+    Some(big(&big_integer(a)?, &big_integer(b)?).into())
 }
 
-pub fn word_add(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
-    let result = a + b;
+pub fn word_add(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = pop_number(interpreter, "+")?;
+    let b = pop_number(interpreter, "+")?;
+    let result = integer_arithmetic(&b, &a, i128::checked_add, BigInt::add)
+        .unwrap_or_else(|| (f64::from(b) + f64::from(a)).into());
 
-    interpreter.push_value(result.into())
+    interpreter.push_value(result);
+
+    Ok(())
 }
 
-pub fn word_subtract(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
-    let result = b - a;
+pub fn word_subtract(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = pop_number(interpreter, "-")?;
+    let b = pop_number(interpreter, "-")?;
+    let result = integer_arithmetic(&b, &a, i128::checked_sub, BigInt::sub)
+        .unwrap_or_else(|| (f64::from(b) - f64::from(a)).into());
+
+    interpreter.push_value(result);
 
-    interpreter.push_value(result.into())
+    Ok(())
 }
 
-pub fn word_multiply(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
-    let result = b * a;
+pub fn word_multiply(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = pop_number(interpreter, "*")?;
+    let b = pop_number(interpreter, "*")?;
+    let result = integer_arithmetic(&b, &a, i128::checked_mul, BigInt::mul)
+        .unwrap_or_else(|| (f64::from(b) * f64::from(a)).into());
+
+    interpreter.push_value(result);
 
-    interpreter.push_value(result.into())
+    Ok(())
 }
 
-pub fn word_divide(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+// NOTE: Division stays inexact, `1 2 /` is 0.5
+pub fn word_divide(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a: f64 = pop_number(interpreter, "/")?.into();
+    let b: f64 = pop_number(interpreter, "/")?.into();
     let result = b / a;
 
-    interpreter.push_value(result.into())
+    interpreter.push_value(result.into());
+
+    Ok(())
+}
+
+/// Pops the operands of a comparison word and compares the second with the
+/// top one
+fn pop_comparison(
+    interpreter: &mut Interpreter,
+    word: &str,
+) -> Result<Option<Ordering>, RuntimeError> {
+    let a = interpreter.pop_value()?;
+    let b = interpreter.pop_value()?;
+    compare_values(word, &b, &a)
+}
+
+pub fn word_less(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let ordering = pop_comparison(interpreter, "<")?;
+    interpreter.push_value((ordering == Some(Ordering::Less)).into());
+
+    Ok(())
+}
+
+pub fn word_more(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let ordering = pop_comparison(interpreter, ">")?;
+    interpreter.push_value((ordering == Some(Ordering::Greater)).into());
+
+    Ok(())
+}
+
+pub fn word_equal(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = interpreter.pop_value()?;
+    let b = interpreter.pop_value()?;
+    let equal = equal_values("==", &b, &a)?;
+    interpreter.push_value(equal.into());
+
+    Ok(())
+}
+
+pub fn word_less_or_equal(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let ordering = pop_comparison(interpreter, "<=")?;
+    interpreter.push_value(matches!(ordering, Some(Ordering::Less | Ordering::Equal)).into());
+
+    Ok(())
+}
+
+pub fn word_more_or_equal(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let ordering = pop_comparison(interpreter, ">=")?;
+    interpreter.push_value(matches!(ordering, Some(Ordering::Greater | Ordering::Equal)).into());
+
+    Ok(())
+}
+
+pub fn word_not_equal(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = interpreter.pop_value()?;
+    let b = interpreter.pop_value()?;
+    let equal = equal_values("!=", &b, &a)?;
+    interpreter.push_value((!equal).into());
+
+    Ok(())
+}
+
+fn conversion_error(word: &str, value: &StackValue, target: &str) -> RuntimeError {
+    RuntimeError::Conversion(format!(
+        "`{}` can't convert {} to {}",
+        word,
+        value.repr(),
+        target
+    ))
+}
+
+/// Float truncated toward zero if the result is in `min..max`
+fn truncate_float(value: f64, min: f64, max: f64) -> Option<f64> {
+    let truncated = value.trunc();
+    // NOTE: Comparisons with NaN are always false
+    (truncated >= min && truncated < max).then_some(truncated)
+}
+
+pub fn word_to_int(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = interpreter.pop_value()?;
+    let result = match &value {
+        StackValue::UnsignedInt(int) => i64::try_from(*int).ok(),
+        StackValue::SignedInt(int) => Some(*int),
+        // NOTE: Big integers never fit in 64 bits
+        StackValue::BigInt(_) => None,
+        StackValue::Float(flt) => {
+            truncate_float(*flt, i64::MIN as f64, -(i64::MIN as f64)).map(|flt| flt as i64)
+        }
+        StackValue::Bool(bool) => Some(*bool as i64),
+        StackValue::String(str) => str.parse().ok(),
+        StackValue::List(_) | StackValue::Quotation(_) => {
+            return Err(type_error("to_int", "number, string or bool", &value))
+        }
+    };
+    let int = result.ok_or_else(|| conversion_error("to_int", &value, "a signed integer"))?;
+    interpreter.push_value(StackValue::SignedInt(int));
+
+    Ok(())
+}
+
+pub fn word_to_uint(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = interpreter.pop_value()?;
+    let result = match &value {
+        StackValue::UnsignedInt(int) => Some(*int),
+        StackValue::SignedInt(int) => u64::try_from(*int).ok(),
+        StackValue::BigInt(_) => None,
+        StackValue::Float(flt) => truncate_float(*flt, 0.0, u64::MAX as f64).map(|flt| flt as u64),
+        StackValue::Bool(bool) => Some(*bool as u64),
+        StackValue::String(str) => str.parse().ok(),
+        StackValue::List(_) | StackValue::Quotation(_) => {
+            return Err(type_error("to_uint", "number, string or bool", &value))
+        }
+    };
+    let int = result.ok_or_else(|| conversion_error("to_uint", &value, "an unsigned integer"))?;
+    interpreter.push_value(StackValue::UnsignedInt(int));
+
+    Ok(())
+}
+
+pub fn word_to_float(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = interpreter.pop_value()?;
+    let result = match &value {
+        StackValue::UnsignedInt(_)
+        | StackValue::SignedInt(_)
+        | StackValue::BigInt(_)
+        | StackValue::Float(_) => Some(f64::from(value.clone())),
+        StackValue::Bool(bool) => Some(*bool as u8 as f64),
+        StackValue::String(str) => str.parse().ok(),
+        StackValue::List(_) | StackValue::Quotation(_) => {
+            return Err(type_error("to_float", "number, string or bool", &value))
+        }
+    };
+    let flt =
+        result.ok_or_else(|| conversion_error("to_float", &value, "a floating point number"))?;
+    interpreter.push_value(flt.into());
+
+    Ok(())
+}
+
+pub fn word_to_string(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = interpreter.pop_value()?;
+    interpreter.push_value(value.to_string().into());
+
+    Ok(())
+}
+
+pub fn word_to_bool(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value: bool = interpreter.pop_value()?.into();
+    interpreter.push_value(value.into());
+
+    Ok(())
+}
+
+fn pop_number(interpreter: &mut Interpreter, word: &str) -> Result<StackValue, RuntimeError> {
+    match interpreter.pop_value()? {
+        value @ (StackValue::UnsignedInt(_)
+        | StackValue::SignedInt(_)
+        | StackValue::BigInt(_)
+        | StackValue::Float(_)) => Ok(value),
+        other => Err(type_error(word, "number", &other)),
+    }
+}
+
+/// Integer operand of the bitwise and integer division words
+fn integer_operand(word: &str, value: &StackValue) -> Result<i128, RuntimeError> {
+    match value {
+        StackValue::BigInt(_) => Err(RuntimeError::Type(format!(
+            "`{}` only works on integers that fit in 64 bits",
+            word
+        ))),
+        value => small_integer(value).ok_or_else(|| type_error(word, "integers", value)),
+    }
+}
+
+/// Exact comparison of an integer with a float
+fn compare_integer_float(int: &BigInt, flt: f64) -> Option<Ordering> {
+    if flt.is_nan() {
+        return None;
+    }
+    if flt.is_infinite() {
+        return Some(0.0_f64.total_cmp(&flt));
+    }
+    let truncated = flt.trunc();
+    let ordering = int.cmp(&BigInt::from_integral_f64(truncated));
+    // NOTE: On a tie the fractional part decides
+    Some(ordering.then(truncated.total_cmp(&flt)))
+}
+
+/// Numeric comparison, exact between integers and between an integer and a
+/// float. `None` if either is NaN
+fn compare_numbers(a: &StackValue, b: &StackValue) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (small_integer(a), small_integer(b)) {
+        return Some(a.cmp(&b));
+    }
+    match (a, b) {
+        (StackValue::Float(a), StackValue::Float(b)) => a.partial_cmp(b),
+        (StackValue::Float(a), b) => {
+            compare_integer_float(&big_integer(b)?, *a).map(Ordering::reverse)
+        }
+        (a, StackValue::Float(b)) => compare_integer_float(&big_integer(a)?, *b),
+        (a, b) => Some(big_integer(a)?.cmp(&big_integer(b)?)),
+    }
+}
+
+/// Ordering of the comparison words: numbers by value, strings
+/// lexicographically, false before true and lists item by item
+fn compare_values(
+    word: &str,
+    a: &StackValue,
+    b: &StackValue,
+) -> Result<Option<Ordering>, RuntimeError> {
+    match (a, b) {
+        (StackValue::String(a), StackValue::String(b)) => Ok(Some(a.cmp(b))),
+        (StackValue::Bool(a), StackValue::Bool(b)) => Ok(Some(a.cmp(b))),
+        (StackValue::List(a), StackValue::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match compare_values(word, a, b)? {
+                    Some(Ordering::Equal) => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(Some(a.len().cmp(&b.len())))
+        }
+        (a, b)
+            if a.argument_type() == ArgumentType::Number
+                && b.argument_type() == ArgumentType::Number =>
+        {
+            Ok(compare_numbers(a, b))
+        }
+        (a, b) => Err(RuntimeError::Type(format!(
+            "`{}` can't compare {} and {}",
+            word,
+            a.argument_type(),
+            b.argument_type()
+        ))),
+    }
+}
+
+/// Equality of `==` and `!=`, quotations can only be compared this way
+fn equal_values(word: &str, a: &StackValue, b: &StackValue) -> Result<bool, RuntimeError> {
+    match (a, b) {
+        (StackValue::List(a), StackValue::List(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for (a, b) in a.iter().zip(b) {
+                if !equal_values(word, a, b)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (StackValue::Quotation(a), StackValue::Quotation(b)) => Ok(a == b),
+        (a, b) => Ok(compare_values(word, a, b)? == Some(Ordering::Equal)),
+    }
+}
+
+/// Logical on two bools, bitwise on two integers
+fn logical_or_bitwise(
+    interpreter: &mut Interpreter,
+    word: &str,
+    logical: fn(bool, bool) -> bool,
+    bitwise: fn(i128, i128) -> i128,
+) -> Result<(), RuntimeError> {
+    let a = interpreter.pop_value()?;
+    let b = interpreter.pop_value()?;
+    let result = match (&b, &a) {
+        (StackValue::Bool(b), StackValue::Bool(a)) => logical(*b, *a).into(),
+        (StackValue::Bool(_), other) | (other, StackValue::Bool(_)) => {
+            return Err(type_error(word, "bool", other))
+        }
+        (b, a) => bitwise(integer_operand(word, b)?, integer_operand(word, a)?).into(),
+    };
+    interpreter.push_value(result);
+
+    Ok(())
+}
+
+pub fn word_and(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    logical_or_bitwise(interpreter, "and", |b, a| b && a, |b, a| b & a)
+}
+
+pub fn word_or(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    logical_or_bitwise(interpreter, "or", |b, a| b || a, |b, a| b | a)
+}
+
+pub fn word_xor(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    logical_or_bitwise(interpreter, "xor", |b, a| b != a, |b, a| b ^ a)
+}
+
+// NOTE: Integers behave as if they had infinitely many bits, so `5 not` is -6
+pub fn word_not(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let result = match interpreter.pop_value()? {
+        StackValue::Bool(bool) => (!bool).into(),
+        value => (!integer_operand("not", &value)?).into(),
+    };
+    interpreter.push_value(result);
+
+    Ok(())
+}
+
+/// Pops the count and the integer of `shl` and `shr`
+fn pop_shift(interpreter: &mut Interpreter, word: &str) -> Result<(i128, u32), RuntimeError> {
+    let count = integer_operand(word, &interpreter.pop_value()?)?;
+    let value = integer_operand(word, &interpreter.pop_value()?)?;
+    match u32::try_from(count) {
+        Ok(count) if count < 64 => Ok((value, count)),
+        _ => Err(RuntimeError::Arithmetic(format!(
+            "`{}` can't shift by {}, the count must be between 0 and 63",
+            word, count
+        ))),
+    }
+}
+
+pub fn word_shift_left(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let (value, count) = pop_shift(interpreter, "shl")?;
+    // NOTE: Can't overflow, the value fits in 65 bits and the count is below 64
+    interpreter.push_value((value * (1 << count)).into());
+
+    Ok(())
+}
+
+pub fn word_shift_right(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let (value, count) = pop_shift(interpreter, "shr")?;
+    interpreter.push_value((value >> count).into());
+
+    Ok(())
+}
+
+/// Dividend and divisor of `mod` and `divmod`
+enum Division {
+    Integers(i128, i128),
+    Floats(f64, f64),
+}
+
+fn pop_division(interpreter: &mut Interpreter, word: &str) -> Result<Division, RuntimeError> {
+    let a = pop_number(interpreter, word)?;
+    let b = pop_number(interpreter, word)?;
+    if matches!(a, StackValue::Float(_)) || matches!(b, StackValue::Float(_)) {
+        return Ok(Division::Floats(b.into(), a.into()));
+    }
+
+    let divisor = integer_operand(word, &a)?;
+    let dividend = integer_operand(word, &b)?;
+    if divisor == 0 {
+        return Err(RuntimeError::Arithmetic(format!(
+            "`{}` divided by zero",
+            word
+        )));
+    }
+    Ok(Division::Integers(dividend, divisor))
+}
+
+pub fn word_mod(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let result = match pop_division(interpreter, "mod")? {
+        Division::Integers(dividend, divisor) => (dividend % divisor).into(),
+        Division::Floats(dividend, divisor) => (dividend % divisor).into(),
+    };
+    interpreter.push_value(result);
+
+    Ok(())
+}
+
+pub fn word_divmod(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let (quotient, remainder) = match pop_division(interpreter, "divmod")? {
+        Division::Integers(dividend, divisor) => {
+            ((dividend / divisor).into(), (dividend % divisor).into())
+        }
+        Division::Floats(dividend, divisor) => (
+            (dividend / divisor).trunc().into(),
+            (dividend % divisor).into(),
+        ),
+    };
+    interpreter.push_value(quotient);
+    interpreter.push_value(remainder);
+
+    Ok(())
 }
 
-pub fn word_less(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+pub fn word_abs(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let result = match pop_number(interpreter, "abs")? {
+        StackValue::Float(flt) => flt.abs().into(),
+        StackValue::BigInt(int) => int.abs().into(),
+        value => small_integer(&value).unwrap().abs().into(),
+    };
+    interpreter.push_value(result);
 
-    interpreter.push_value((b < a).into())
+    Ok(())
 }
 
-pub fn word_more(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+pub fn word_neg(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let result = match pop_number(interpreter, "neg")? {
+        StackValue::Float(flt) => (-flt).into(),
+        StackValue::BigInt(int) => int.neg().into(),
+        value => (-small_integer(&value).unwrap()).into(),
+    };
+    interpreter.push_value(result);
 
-    interpreter.push_value((b > a).into())
+    Ok(())
 }
 
-pub fn word_equal(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+pub fn word_min(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = pop_number(interpreter, "min")?;
+    let b = pop_number(interpreter, "min")?;
+    let result = match compare_numbers(&a, &b) {
+        Some(Ordering::Less) => a,
+        _ => b,
+    };
+    interpreter.push_value(result);
 
-    interpreter.push_value((b == a).into())
+    Ok(())
 }
 
-pub fn word_less_or_equal(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+pub fn word_max(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = pop_number(interpreter, "max")?;
+    let b = pop_number(interpreter, "max")?;
+    let result = match compare_numbers(&a, &b) {
+        Some(Ordering::Greater) => a,
+        _ => b,
+    };
+    interpreter.push_value(result);
 
-    interpreter.push_value((b <= a).into())
+    Ok(())
 }
 
-pub fn word_more_or_equal(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+/// Most digits an exact `pow` may produce, bigger results take too long to
+/// multiply out
+const MAX_POW_DIGITS: usize = 100_000;
+
+// NOTE: Integers raised to a non-negative integer stay exact, anything else
+// is computed with floats
+pub fn word_pow(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let exponent = pop_number(interpreter, "pow")?;
+    let base = pop_number(interpreter, "pow")?;
+
+    let exact = match (big_integer(&base), small_integer(&exponent)) {
+        (Some(base), Some(exponent)) if exponent >= 0 => {
+            // NOTE: The result has at least `(bits - 1) * exponent` bits, so
+            // this never rejects a result that would fit
+            let digits =
+                (base.bits().saturating_sub(1) as f64 * exponent as f64 * LOG10_2) as usize;
+            interpreter.check_value_size(digits)?;
+            if digits > MAX_POW_DIGITS {
+                return Err(RuntimeError::Arithmetic(format!(
+                    "`pow` result would have more than {} digits",
+                    MAX_POW_DIGITS
+                )));
+            }
+            Some(base.try_pow(exponent as u64, || interpreter.check_deadline())?)
+        }
+        _ => None,
+    };
+    let result = match exact {
+        Some(int) => int.into(),
+        None => f64::from(base).powf(f64::from(exponent)).into(),
+    };
+    interpreter.push_value(result);
 
-    interpreter.push_value((b <= a).into())
+    Ok(())
 }
 
-pub fn word_not_equal(interpreter: &mut Interpreter) {
-    let a: f64 = interpreter.pop_value().unwrap().into();
-    let b: f64 = interpreter.pop_value().unwrap().into();
+pub fn word_sqrt(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value: f64 = pop_number(interpreter, "sqrt")?.into();
+    interpreter.push_value(value.sqrt().into());
 
-    interpreter.push_value((b != a).into())
+    Ok(())
 }
 
-pub fn word_dup(interpreter: &mut Interpreter) {
-    let t = interpreter.pop_value().unwrap();
+pub fn word_dup(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let t = interpreter.pop_value()?;
     interpreter.push_value(t.clone());
     interpreter.push_value(t.clone());
+
+    Ok(())
+}
+
+pub fn word_swap(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let a = interpreter.pop_value()?;
+    let b = interpreter.pop_value()?;
+    interpreter.push_value(a);
+    interpreter.push_value(b);
+
+    Ok(())
+}
+
+pub fn word_exit(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = pop_number(interpreter, "exit")?;
+    let code = small_integer(&value)
+        .and_then(|int| i32::try_from(int).ok())
+        .ok_or_else(|| conversion_error("exit", &value, "a 32 bit exit code"))?;
+    interpreter.exit(code);
+
+    Ok(())
 }
 
-pub fn word_print(interpreter: &mut Interpreter) {
-    let t = interpreter.pop_value().unwrap();
+pub fn word_print(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let t = interpreter.pop_value()?;
     writeln!(interpreter.output, "{}", t).unwrap();
+
+    Ok(())
 }
 
-pub fn word_get_line(interpreter: &mut Interpreter) {
+/// Reads the next line without surrounding whitespace
+fn read_line(interpreter: &mut Interpreter) -> Result<String, RuntimeError> {
     let mut buf = vec![];
-    let _ = interpreter.input.read_until(b'\n', &mut buf).unwrap();
-    let buf: String = String::from_utf8(buf).unwrap().trim().to_string();
-    interpreter.push_value(buf.into())
+    let read = interpreter
+        .input
+        .read_until(b'\n', &mut buf)
+        .map_err(|e| RuntimeError::Io(format!("Could not read the input: {}", e)))?;
+    if read == 0 {
+        return Err(InputError::Eof.into());
+    }
+    Ok(String::from_utf8_lossy(&buf).trim().to_string())
 }
 
-pub fn word_get_int(interpreter: &mut Interpreter) {
-    let mut buf = vec![];
-    let _ = interpreter.input.read_until(b'\n', &mut buf).unwrap();
-    let buf: String = String::from_utf8(buf).unwrap().trim().to_string();
+fn parse_line<T: FromStr>(
+    interpreter: &mut Interpreter,
+    expected: &'static str,
+) -> Result<T, RuntimeError> {
+    let line = read_line(interpreter)?;
+    line.parse()
+        .map_err(|_| InputError::Malformed { expected, line }.into())
+}
+
+pub fn word_get_line(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let line = read_line(interpreter)?;
+    interpreter.push_value(line.into());
 
-    interpreter.push_value(buf.parse::<i64>().unwrap().into())
+    Ok(())
 }
 
-pub fn word_get_uint(interpreter: &mut Interpreter) {
-    let mut buf = vec![];
-    let _ = interpreter.input.read_until(b'\n', &mut buf).unwrap();
-    let buf: String = String::from_utf8(buf).unwrap().trim().to_string();
-    eprintln!("{:?}", buf.trim());
-    interpreter.push_value(buf.parse::<u64>().unwrap().into())
+pub fn word_get_int(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value: i64 = parse_line(interpreter, "a signed integer")?;
+    interpreter.push_value(value.into());
+
+    Ok(())
+}
+
+pub fn word_get_uint(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value: u64 = parse_line(interpreter, "an unsigned integer")?;
+    interpreter.push_value(value.into());
+
+    Ok(())
 }
 
-pub fn word_get_float(interpreter: &mut Interpreter) {
+pub fn word_get_float(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value: f64 = parse_line(interpreter, "a floating point number")?;
+    interpreter.push_value(value.into());
+
+    Ok(())
+}
+
+pub fn word_read_all(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
     let mut buf = vec![];
-    let _ = interpreter.input.read(&mut buf).unwrap();
-    let _ = interpreter.input.read_until(b'\n', &mut buf).unwrap();
-    let buf: String = String::from_utf8(buf).unwrap().trim().to_string();
-    interpreter.push_value(buf.trim_end().parse::<f64>().unwrap().into())
+    // NOTE: Reading one byte past the size limit is enough to know it was hit
+    let limit = interpreter
+        .limits()
+        .value_size
+        .map_or(u64::MAX, |limit| limit as u64 + 1);
+    Read::take(interpreter.input.as_mut(), limit)
+        .read_to_end(&mut buf)
+        .map_err(|e| RuntimeError::Io(format!("Could not read the input: {}", e)))?;
+    interpreter.check_value_size(buf.len())?;
+    interpreter.check_deadline()?;
+    interpreter.push_value(String::from_utf8_lossy(&buf).into_owned().into());
+
+    Ok(())
+}
+
+pub fn word_eof(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let eof = interpreter
+        .input
+        .fill_buf()
+        .map_err(|e| RuntimeError::Io(format!("Could not read the input: {}", e)))?
+        .is_empty();
+    interpreter.push_value(eof.into());
+
+    Ok(())
 }
 
-pub fn word_if(interpreter: &mut Interpreter) {
-    let condition: bool = interpreter.pop_value().unwrap().into();
+pub fn word_if(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let condition: bool = interpreter.pop_value()?.into();
 
     if !condition {
         if let ILToken::If(ip) = interpreter.tokens[interpreter.position] {
             interpreter.position = ip;
         }
     }
+
+    Ok(())
+}
+
+pub fn word_assert(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let value = interpreter.pop_value()?;
+    if !bool::from(value.clone()) {
+        return Err(RuntimeError::Assertion(format!(
+            "{} is false",
+            value.repr()
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn word_assert_eq(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let expected = interpreter.pop_value()?;
+    let actual = interpreter.pop_value()?;
+    if !equal_values("assert_eq", &actual, &expected)? {
+        return Err(RuntimeError::Assertion(format!(
+            "{} != {}",
+            actual.repr(),
+            expected.repr()
+        )));
+    }
+
+    Ok(())
+}
+
+fn type_error(word: &str, expected: &str, actual: &StackValue) -> RuntimeError {
+    RuntimeError::Type(format!(
+        "`{}` expects {} but got {}",
+        word,
+        expected,
+        actual.argument_type()
+    ))
+}
+
+fn pop_list(interpreter: &mut Interpreter, word: &str) -> Result<Vec<StackValue>, RuntimeError> {
+    match interpreter.pop_value()? {
+        StackValue::List(items) => Ok(items),
+        other => Err(type_error(word, "list", &other)),
+    }
+}
+
+fn pop_quotation(interpreter: &mut Interpreter, word: &str) -> Result<Quotation, RuntimeError> {
+    match interpreter.pop_value()? {
+        StackValue::Quotation(quotation) => Ok(quotation),
+        other => Err(type_error(word, "quotation", &other)),
+    }
+}
+
+pub fn word_call(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "call")?;
+    interpreter.call_quotation(quotation)
+}
+
+pub fn word_map(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "map")?;
+    let items = pop_list(interpreter, "map")?;
+
+    let mut result = vec![];
+    for item in items {
+        interpreter.push_value(item);
+        interpreter.call_quotation(quotation.clone())?;
+        if interpreter.exit_code().is_some() {
+            return Ok(());
+        }
+        result.push(interpreter.pop_value()?);
+    }
+    interpreter.push_value(StackValue::List(result));
+
+    Ok(())
+}
+
+pub fn word_filter(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "filter")?;
+    let items = pop_list(interpreter, "filter")?;
+
+    let mut result = vec![];
+    for item in items {
+        interpreter.push_value(item.clone());
+        interpreter.call_quotation(quotation.clone())?;
+        if interpreter.exit_code().is_some() {
+            return Ok(());
+        }
+        if bool::from(interpreter.pop_value()?) {
+            result.push(item);
+        }
+    }
+    interpreter.push_value(StackValue::List(result));
+
+    Ok(())
+}
+
+pub fn word_fold(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "fold")?;
+    let initial = interpreter.pop_value()?;
+    let items = pop_list(interpreter, "fold")?;
+
+    interpreter.push_value(initial);
+    for item in items {
+        interpreter.push_value(item);
+        interpreter.call_quotation(quotation.clone())?;
+        if interpreter.exit_code().is_some() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn word_each(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "each")?;
+    let items = pop_list(interpreter, "each")?;
+
+    for item in items {
+        interpreter.push_value(item);
+        interpreter.call_quotation(quotation.clone())?;
+        if interpreter.exit_code().is_some() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn word_times(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let quotation = pop_quotation(interpreter, "times")?;
+    let count: f64 = pop_number(interpreter, "times")?.into();
+
+    for _ in 0..count.max(0.0) as u64 {
+        interpreter.call_quotation(quotation.clone())?;
+        if interpreter.exit_code().is_some() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+fn pop_string(interpreter: &mut Interpreter, word: &str) -> Result<String, RuntimeError> {
+    match interpreter.pop_value()? {
+        StackValue::String(str) => Ok(str),
+        other => Err(type_error(word, "string", &other)),
+    }
+}
+
+pub fn word_read_file(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let path = pop_string(interpreter, "read_file")?;
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| RuntimeError::Io(format!("Could not read `{}`: {}", path, e)))?;
+    interpreter.push_value(contents.into());
+
+    Ok(())
+}
+
+pub fn word_write_file(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let path = pop_string(interpreter, "write_file")?;
+    let contents = interpreter.pop_value()?.to_string();
+    std::fs::write(&path, contents)
+        .map_err(|e| RuntimeError::Io(format!("Could not write `{}`: {}", path, e)))
+}
+
+pub fn word_env(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let name = pop_string(interpreter, "env")?;
+    interpreter.push_value(std::env::var(name).unwrap_or_default().into());
+
+    Ok(())
+}
+
+pub fn word_now(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    interpreter.push_value(since_epoch.as_secs_f64().into());
+
+    Ok(())
+}
+
+pub fn word_random(interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+    use std::hash::{BuildHasher, Hasher};
+
+    // NOTE: Every `RandomState` is seeded differently, which is good enough
+    // without pulling in a dependency
+    let bits = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    interpreter.push_value(((bits >> 11) as f64 / (1_u64 << 53) as f64).into());
+
+    Ok(())
 }
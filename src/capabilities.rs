@@ -0,0 +1,75 @@
+use core::fmt;
+
+/// Access to the host that a builtin word can need
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Capability {
+    Stdin,
+    Stdout,
+    FsRead,
+    FsWrite,
+    Env,
+    Clock,
+    Random,
+}
+
+impl Capability {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Stdin => "stdin",
+            Self::Stdout => "stdout",
+            Self::FsRead => "fs-read",
+            Self::FsWrite => "fs-write",
+            Self::Env => "env",
+            Self::Clock => "clock",
+            Self::Random => "random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Set of capabilities granted to a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self(u8::MAX)
+    }
+
+    /// No access to the host at all
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn with(self, capability: Capability) -> Self {
+        Self(self.0 | capability.bit())
+    }
+
+    pub fn allows(self, capability: Capability) -> bool {
+        self.0 & capability.bit() != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets() {
+        let capabilities = Capabilities::none()
+            .with(Capability::Stdout)
+            .with(Capability::Clock);
+
+        assert!(capabilities.allows(Capability::Stdout));
+        assert!(capabilities.allows(Capability::Clock));
+        assert!(!capabilities.allows(Capability::Stdin));
+        assert!(!Capabilities::none().allows(Capability::Random));
+        assert!(Capabilities::all().allows(Capability::FsWrite));
+    }
+}
@@ -0,0 +1,820 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    built_in_words,
+    diagnostics::Diagnostic,
+    lexer::{ArgumentType, FunctionInfo, ILToken, Span},
+    lint,
+};
+
+/// Statically known type of a stack value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Value(ArgumentType),
+    Any,
+}
+
+impl Type {
+    fn merge(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else {
+            Type::Any
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(t) => write!(f, "{}", t),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// Values a word takes from the stack and values it leaves, bottom first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Effect {
+    pub inputs: Vec<Type>,
+    pub outputs: Vec<Type>,
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for input in &self.inputs {
+            write!(f, " {}", input)?;
+        }
+        write!(f, " --")?;
+        for output in &self.outputs {
+            write!(f, " {}", output)?;
+        }
+        write!(f, " )")
+    }
+}
+
+/// Item of a builtin signature, either a type or a variable standing for a
+/// value of any type
+#[derive(Debug, Clone, Copy)]
+enum Slot<'s> {
+    Type(Type),
+    Var(&'s str),
+}
+
+impl<'s> Slot<'s> {
+    fn parse(name: &'s str) -> Self {
+        match ArgumentType::from_str(name) {
+            Ok(ArgumentType::Nothing) | Err(_) => Self::Var(name),
+            Ok(t) => Self::Type(Type::Value(t)),
+        }
+    }
+}
+
+/// Splits a signature like `( number number -- bool )` into its inputs and
+/// outputs
+pub fn parse_signature(signature: &str) -> (Vec<&str>, Vec<&str>) {
+    let inner = signature
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    let (inputs, outputs) = inner.split_once("--").unwrap_or((inner, ""));
+    (
+        inputs.split_whitespace().collect(),
+        outputs.split_whitespace().collect(),
+    )
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    stack: Vec<Type>,
+    /// Values taken from below the stack the function started with
+    inputs: Vec<Type>,
+    in_function: bool,
+    /// Types of the local variables, indexed by their frame slot
+    locals: Vec<Type>,
+    /// Set once the stack can't be known statically anymore
+    unknown: bool,
+    /// Set after `return`, the code until the end of the block never runs
+    returned: bool,
+}
+
+impl State {
+    fn depth(&self) -> isize {
+        self.stack.len() as isize - self.inputs.len() as isize
+    }
+
+    /// Stack effect of a function leaving with this state
+    fn effect(&self, info: &FunctionInfo) -> Effect {
+        Effect {
+            inputs: self
+                .inputs
+                .iter()
+                .copied()
+                .chain(info.inputs.iter().map(|t| Type::Value(*t)))
+                .collect(),
+            outputs: self.stack.clone(),
+        }
+    }
+}
+
+/// Effect of a function with several ways to leave it, unknown if they
+/// don't agree on the number of values
+fn merge_effects(exits: &[Effect]) -> Option<Effect> {
+    let (first, rest) = exits.split_first()?;
+    let mut merged = first.clone();
+    for effect in rest {
+        if effect.inputs.len() != merged.inputs.len()
+            || effect.outputs.len() != merged.outputs.len()
+        {
+            return None;
+        }
+        for (t, other) in merged.inputs.iter_mut().zip(&effect.inputs) {
+            *t = t.merge(*other);
+        }
+        for (t, other) in merged.outputs.iter_mut().zip(&effect.outputs) {
+            *t = t.merge(*other);
+        }
+    }
+    Some(merged)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockKind {
+    If,
+    /// Runs on a fresh stack and leaves the program's stack alone
+    Test,
+    /// Only scopes local variables
+    Let,
+    /// Code that isn't run where it's written, checked on an unknown stack
+    Quotation,
+    List,
+}
+
+#[derive(Debug)]
+struct Block {
+    start: Span,
+    before: State,
+    kind: BlockKind,
+}
+
+impl Block {
+    fn unclosed(&self) -> Diagnostic {
+        let (keyword, closer) = match self.kind {
+            BlockKind::If => ("if", "end"),
+            BlockKind::Test => ("test", "end"),
+            BlockKind::Let => ("let", "end"),
+            BlockKind::Quotation => ("[", "]"),
+            BlockKind::List => ("{", "}"),
+        };
+        Diagnostic::error(
+            self.start,
+            format!("`{}` without a matching `{}`", keyword, closer),
+        )
+    }
+}
+
+struct Checker<'a> {
+    tokens: &'a [ILToken],
+    spans: &'a [Span],
+    diagnostics: Vec<Diagnostic>,
+    defined: HashSet<&'a str>,
+    effects: HashMap<&'a str, Option<Effect>>,
+    /// Type of the values every global variable holds
+    variables: HashMap<&'a str, Type>,
+}
+
+impl<'a> Checker<'a> {
+    fn span(&self, i: usize) -> Span {
+        self.spans.get(i).copied().unwrap_or_default()
+    }
+
+    fn pop(&mut self, state: &mut State, expected: Type) -> Type {
+        match state.stack.pop() {
+            Some(t) => t,
+            None => {
+                if state.in_function {
+                    state.inputs.insert(0, expected);
+                }
+                expected
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        state: &mut State,
+        word: &str,
+        span: Span,
+        inputs: &[Slot],
+        outputs: &[Slot],
+    ) {
+        if !state.in_function && !state.unknown && state.stack.len() < inputs.len() {
+            self.diagnostics.push(Diagnostic::error(
+                span,
+                format!(
+                    "Stack underflow: `{}` needs {} value(s) but the stack has {}",
+                    word,
+                    inputs.len(),
+                    state.stack.len()
+                ),
+            ));
+        }
+
+        let mut bindings: HashMap<&str, Type> = HashMap::new();
+        for input in inputs.iter().rev() {
+            match input {
+                Slot::Type(expected) => {
+                    let actual = self.pop(state, *expected);
+                    if let (Type::Value(expected), Type::Value(actual)) = (expected, actual) {
+                        if *expected != actual && !state.unknown {
+                            self.diagnostics.push(Diagnostic::error(
+                                span,
+                                format!("`{}` expects {} but got {}", word, expected, actual),
+                            ));
+                        }
+                    }
+                }
+                Slot::Var(name) => {
+                    let actual = self.pop(state, Type::Any);
+                    bindings.insert(name, actual);
+                }
+            }
+        }
+
+        for output in outputs {
+            state.stack.push(match output {
+                Slot::Type(t) => *t,
+                Slot::Var(name) => bindings.get(name).copied().unwrap_or(Type::Any),
+            });
+        }
+    }
+
+    fn call(&mut self, state: &mut State, name: &str, span: Span) {
+        if let Some(builtin) = built_in_words::find_builtin(name) {
+            let (inputs, outputs) = parse_signature(builtin.signature);
+            let inputs: Vec<Slot> = inputs.into_iter().map(Slot::parse).collect();
+            let outputs: Vec<Slot> = outputs.into_iter().map(Slot::parse).collect();
+            self.apply(state, name, span, &inputs, &outputs);
+            // NOTE: What these leave on the stack depends on the quotation
+            if matches!(name, "call" | "each" | "times") {
+                state.unknown = true;
+            }
+            return;
+        }
+
+        if !self.defined.contains(name) {
+            self.diagnostics
+                .push(Diagnostic::error(span, format!("Unknown word: {}", name)));
+            state.unknown = true;
+            return;
+        }
+
+        match self.effects.get(name).cloned().flatten() {
+            Some(effect) => {
+                let inputs: Vec<Slot> = effect.inputs.into_iter().map(Slot::Type).collect();
+                let outputs: Vec<Slot> = effect.outputs.into_iter().map(Slot::Type).collect();
+                self.apply(state, name, span, &inputs, &outputs);
+            }
+            // NOTE: Recursive calls, or calls to functions that could not be
+            // analyzed themselves
+            None => state.unknown = true,
+        }
+    }
+
+    fn check_return_type(
+        &mut self,
+        name: &str,
+        return_type: ArgumentType,
+        span: Span,
+        effect: &Effect,
+    ) {
+        let outputs = &effect.outputs;
+        let message = match (return_type, outputs.as_slice()) {
+            (ArgumentType::Nothing, []) => return,
+            (ArgumentType::Nothing, _) => format!(
+                "`{}` is declared to return nothing but leaves {} value(s) on the stack",
+                name,
+                outputs.len()
+            ),
+            (_, [Type::Any]) => return,
+            (_, [Type::Value(actual)]) if *actual == return_type => return,
+            (_, [actual]) => format!(
+                "`{}` is declared to return {} but returns {}",
+                name, return_type, actual
+            ),
+            (_, _) => format!(
+                "`{}` is declared to return {} but leaves {} value(s) on the stack",
+                name,
+                return_type,
+                outputs.len()
+            ),
+        };
+        self.diagnostics
+            .push(Diagnostic::warning(span, message).with_code(lint::RETURN_TYPE_MISMATCH));
+    }
+
+    fn run(&mut self) {
+        let mut state = State::default();
+        let mut top_level: Option<State> = None;
+        let mut function: Option<(usize, &'a FunctionInfo)> = None;
+        let mut blocks: Vec<Block> = vec![];
+        // NOTE: States at every `return` of the current function
+        let mut exits: Vec<State> = vec![];
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            let span = self.span(i);
+            let structural = matches!(
+                token,
+                ILToken::If(_)
+                    | ILToken::Test(..)
+                    | ILToken::Let(..)
+                    | ILToken::End
+                    | ILToken::FuncDef(_)
+                    | ILToken::FuncEnd
+                    | ILToken::Quotation(_)
+                    | ILToken::QuotationEnd
+                    | ILToken::ListStart
+                    | ILToken::ListEnd
+            );
+            if state.returned && !structural {
+                continue;
+            }
+            match token {
+                ILToken::PushString(_) => state.stack.push(Type::Value(ArgumentType::String)),
+                ILToken::PushUnsignedInteger(_)
+                | ILToken::PushSignedInteger(_)
+                | ILToken::PushBigInteger(_)
+                | ILToken::PushFloat(_) => state.stack.push(Type::Value(ArgumentType::Number)),
+                ILToken::PushBoolean(_) => state.stack.push(Type::Value(ArgumentType::Bool)),
+                ILToken::Symbol(name) | ILToken::TailCall(name) => {
+                    self.call(&mut state, name, span)
+                }
+                ILToken::If(_) => {
+                    if !state.returned {
+                        self.apply(&mut state, "if", span, &[Slot::Var("a")], &[]);
+                    }
+                    blocks.push(Block {
+                        start: span,
+                        before: state.clone(),
+                        kind: BlockKind::If,
+                    });
+                }
+                ILToken::Test(..) => {
+                    let before = std::mem::take(&mut state);
+                    blocks.push(Block {
+                        start: span,
+                        before,
+                        kind: BlockKind::Test,
+                    });
+                }
+                ILToken::Let(names, first) => {
+                    if state.locals.len() < first + names.len() {
+                        state.locals.resize(first + names.len(), Type::Any);
+                    }
+                    if !state.returned {
+                        for slot in (*first..first + names.len()).rev() {
+                            state.locals[slot] = self.pop(&mut state, Type::Any);
+                        }
+                    }
+                    blocks.push(Block {
+                        start: span,
+                        before: State::default(),
+                        kind: BlockKind::Let,
+                    });
+                }
+                ILToken::Local(slot) => {
+                    let t = state.locals.get(*slot).copied().unwrap_or(Type::Any);
+                    state.stack.push(t);
+                }
+                ILToken::End => {
+                    let Some(block) = blocks.pop() else {
+                        self.diagnostics.push(Diagnostic::error(
+                            span,
+                            "`end` without a matching `if`, `test` or `let`".to_string(),
+                        ));
+                        continue;
+                    };
+                    if matches!(block.kind, BlockKind::Quotation | BlockKind::List) {
+                        self.diagnostics.push(block.unclosed());
+                        blocks.push(block);
+                        continue;
+                    } else if block.kind == BlockKind::Let {
+                        continue;
+                    } else if block.kind == BlockKind::Test {
+                        state = block.before;
+                    } else if state.returned || block.before.returned {
+                        // NOTE: Only the path skipping the block continues
+                        state = block.before;
+                    } else if state.unknown || block.before.unknown {
+                        state.unknown = true;
+                    } else if state.depth() != block.before.depth() {
+                        self.diagnostics.push(Diagnostic::warning(
+                            block.start,
+                            format!(
+                                "`if` block changes the stack depth by {}, the stack after `end` depends on the condition",
+                                state.depth() - block.before.depth()
+                            ),
+                        ).with_code(lint::UNBALANCED_IF));
+                        state.unknown = true;
+                    } else if state.stack.len() == block.before.stack.len() {
+                        for (t, before) in state.stack.iter_mut().zip(&block.before.stack) {
+                            *t = t.merge(*before);
+                        }
+                    }
+                }
+                ILToken::FuncDef(info) => {
+                    if function.is_some() {
+                        self.diagnostics.push(Diagnostic::error(
+                            span,
+                            "Functions can't be defined inside other functions".to_string(),
+                        ));
+                        continue;
+                    }
+                    function = Some((i, info));
+                    exits.clear();
+                    top_level = Some(std::mem::replace(
+                        &mut state,
+                        State {
+                            stack: info.inputs.iter().map(|t| Type::Value(*t)).collect(),
+                            in_function: true,
+                            ..Default::default()
+                        },
+                    ));
+                }
+                ILToken::FuncEnd => {
+                    let Some((def, info)) = function.take() else {
+                        self.diagnostics.push(Diagnostic::error(
+                            span,
+                            "`fend` without a matching `fdef`".to_string(),
+                        ));
+                        continue;
+                    };
+                    if let Some(block) = blocks.pop() {
+                        self.diagnostics.push(block.unclosed());
+                        blocks.clear();
+                        state.unknown = true;
+                    }
+
+                    if !state.returned && !state.unknown {
+                        self.check_return_type(
+                            &info.name,
+                            info.return_type,
+                            self.span(def),
+                            &state.effect(info),
+                        );
+                    }
+                    if !state.returned {
+                        exits.push(state.clone());
+                    }
+
+                    let undeclared = exits.iter().map(|exit| exit.inputs.len()).max();
+                    let unknown = exits.iter().any(|exit| exit.unknown);
+                    if let Some(undeclared @ 1..) = undeclared {
+                        if !unknown && !info.inputs.is_empty() {
+                            self.diagnostics.push(
+                                Diagnostic::warning(
+                                    self.span(def),
+                                    format!(
+                                        "`{}` takes {} more value(s) than its header declares",
+                                        info.name, undeclared
+                                    ),
+                                )
+                                .with_code(lint::UNDECLARED_INPUTS),
+                            );
+                        }
+                    }
+                    let effects: Vec<Effect> = exits.iter().map(|exit| exit.effect(info)).collect();
+                    let effect = if unknown {
+                        None
+                    } else {
+                        merge_effects(&effects)
+                    };
+                    self.effects.insert(&info.name, effect);
+                    state = top_level.take().unwrap_or_default();
+                }
+                ILToken::Return => {
+                    if let Some((_, info)) = function {
+                        if !state.unknown {
+                            self.check_return_type(
+                                &info.name,
+                                info.return_type,
+                                span,
+                                &state.effect(info),
+                            );
+                        }
+                    }
+                    exits.push(state.clone());
+                    state.returned = true;
+                }
+                ILToken::Quotation(_) => {
+                    let body = State {
+                        in_function: state.in_function,
+                        locals: state.locals.clone(),
+                        unknown: true,
+                        ..Default::default()
+                    };
+                    blocks.push(Block {
+                        start: span,
+                        before: std::mem::replace(&mut state, body),
+                        kind: BlockKind::Quotation,
+                    });
+                }
+                ILToken::ListStart => blocks.push(Block {
+                    start: span,
+                    before: state.clone(),
+                    kind: BlockKind::List,
+                }),
+                ILToken::QuotationEnd | ILToken::ListEnd => {
+                    let (kind, keyword, closer) = match token {
+                        ILToken::QuotationEnd => (BlockKind::Quotation, "[", "]"),
+                        _ => (BlockKind::List, "{", "}"),
+                    };
+                    let Some(block) = blocks.pop_if(|block| block.kind == kind) else {
+                        self.diagnostics.push(Diagnostic::error(
+                            span,
+                            format!("`{}` without a matching `{}`", closer, keyword),
+                        ));
+                        continue;
+                    };
+                    if kind == BlockKind::Quotation {
+                        state = block.before;
+                        state.stack.push(Type::Value(ArgumentType::Quotation));
+                    } else {
+                        state.stack.truncate(block.before.stack.len());
+                        state.stack.push(Type::Value(ArgumentType::List));
+                    }
+                }
+                ILToken::Fetch(name) => {
+                    let t = self.variables.get(name.as_str()).copied();
+                    state.stack.push(t.unwrap_or(Type::Any));
+                }
+                ILToken::Store(name) => {
+                    let t = self.variables.get(name.as_str()).copied();
+                    let slot = match t {
+                        Some(t @ Type::Value(_)) => Slot::Type(t),
+                        _ => Slot::Var("a"),
+                    };
+                    self.apply(&mut state, &format!("{} !", name), span, &[slot], &[]);
+                }
+                ILToken::Comment(_)
+                | ILToken::DocComment(_)
+                | ILToken::Const(..)
+                | ILToken::Var(..) => {}
+            }
+        }
+
+        if let Some((def, info)) = function {
+            self.diagnostics.push(Diagnostic::error(
+                self.span(def),
+                format!("`fdef {}` without a matching `fend`", info.name),
+            ));
+        }
+        for block in blocks {
+            self.diagnostics.push(block.unclosed());
+        }
+    }
+}
+
+/// Result of checking a program
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Stack effect of every function that could be analyzed
+    pub effects: HashMap<String, Effect>,
+}
+
+/// Simulates the program on a stack of types to find underflows, type
+/// mismatches, unknown words, unbalanced blocks and functions whose body
+/// doesn't match their declared return type
+pub fn check(tokens: &[ILToken], spans: &[Span]) -> Report {
+    let defined = tokens
+        .iter()
+        .filter_map(|token| match token {
+            ILToken::FuncDef(info) => Some(info.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let variables = tokens
+        .iter()
+        .filter_map(|token| match token {
+            ILToken::Var(name, declared) => {
+                Some((name.as_str(), declared.map_or(Type::Any, Type::Value)))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut checker = Checker {
+        tokens,
+        spans,
+        diagnostics: vec![],
+        defined,
+        effects: HashMap::new(),
+        variables,
+    };
+    checker.run();
+
+    Report {
+        diagnostics: checker.diagnostics,
+        effects: checker
+            .effects
+            .into_iter()
+            .filter_map(|(name, effect)| Some((name.to_string(), effect?)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostics::Severity, lexer::Lexer};
+
+    fn check_source(src: &str) -> Report {
+        let (tokens, spans) = Lexer::new(src, false).parse_with_spans();
+        check(&tokens, &spans)
+    }
+
+    fn messages(src: &str) -> Vec<(Severity, String)> {
+        check_source(src)
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid_program() {
+        let src = std::fs::read_to_string("main.iod").unwrap();
+
+        assert_eq!(Vec::<(Severity, String)>::new(), messages(&src));
+    }
+
+    #[test]
+    fn underflow() {
+        assert_eq!(
+            vec![(
+                Severity::Error,
+                "Stack underflow: `+` needs 2 value(s) but the stack has 1".to_string()
+            )],
+            messages("1 +")
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        assert_eq!(
+            vec![(
+                Severity::Error,
+                "`*` expects number but got string".to_string()
+            )],
+            messages("\"a\" 2 *")
+        );
+    }
+
+    #[test]
+    fn unknown_word() {
+        assert_eq!(
+            vec![(Severity::Error, "Unknown word: frobnicate".to_string())],
+            messages("1 frobnicate print")
+        );
+    }
+
+    #[test]
+    fn function_effects() {
+        let report = check_source("fdef square : number dup * fend fdef p : ! print fend");
+
+        assert_eq!("( any -- number )", report.effects["square"].to_string());
+        assert_eq!("( any -- )", report.effects["p"].to_string());
+    }
+
+    #[test]
+    fn local_variables() {
+        let report = check_source(
+            "fdef second number string : string let n s in s end fend \
+             fdef twice : number let x in x x + end fend",
+        );
+
+        assert!(report.diagnostics.is_empty());
+        assert_eq!(
+            "( number string -- string )",
+            report.effects["second"].to_string()
+        );
+        assert_eq!("( any -- number )", report.effects["twice"].to_string());
+    }
+
+    #[test]
+    fn variables() {
+        let report = check_source("var count number var any 1 count ! \"a\" any ! \"b\" count !");
+
+        assert_eq!(
+            vec!["`count !` expects number but got string".to_string()],
+            report
+                .diagnostics
+                .iter()
+                .map(|d| d.message.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn early_return() {
+        let report = check_source(
+            "fdef sign : number dup 0 < if drop -1 return end 0 > if 1 return end 0 fend \
+             fdef bad : number 1 if \"no\" return 1 print end 2 fend \
+             fdef uneven : ! 1 if 1 return end fend",
+        );
+
+        assert_eq!("( any -- number )", report.effects["sign"].to_string());
+        assert!(!report.effects.contains_key("uneven"));
+        assert_eq!(
+            vec![
+                "`bad` is declared to return number but returns string".to_string(),
+                "`uneven` is declared to return nothing but leaves 1 value(s) on the stack"
+                    .to_string()
+            ],
+            report
+                .diagnostics
+                .iter()
+                .map(|d| d.message.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn quotations_and_lists() {
+        let report = check_source(
+            "fdef squares : list { 1 2 3 } [ dup * ] map fend \
+             fdef sum list : number 0 [ + ] fold fend \
+             squares sum print { 1 } [ undefined ] each 1 [ ] call",
+        );
+
+        assert_eq!("( -- list )", report.effects["squares"].to_string());
+        assert_eq!("( list -- any )", report.effects["sum"].to_string());
+        assert_eq!(
+            vec!["Unknown word: undefined".to_string()],
+            report
+                .diagnostics
+                .iter()
+                .map(|d| d.message.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn declared_inputs() {
+        let report = check_source("fdef add number number : number + fend \"a\" 1 add");
+
+        assert_eq!(
+            "( number number -- number )",
+            report.effects["add"].to_string()
+        );
+        assert_eq!(
+            vec![Diagnostic::error(
+                Span {
+                    start: 45,
+                    end: 48,
+                    line: 1
+                },
+                "`add` expects number but got string".to_string()
+            )],
+            report.diagnostics
+        );
+    }
+
+    #[test]
+    fn return_type_mismatch() {
+        assert_eq!(
+            vec![
+                (
+                    Severity::Warning,
+                    "`f` is declared to return number but returns string".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "`g` is declared to return nothing but leaves 1 value(s) on the stack"
+                        .to_string()
+                ),
+            ],
+            messages("fdef f : number \"a\" fend fdef g : ! 1 fend")
+        );
+    }
+
+    #[test]
+    fn unbalanced_blocks() {
+        assert_eq!(
+            vec![
+                (
+                    Severity::Warning,
+                    "`if` block changes the stack depth by 1, the stack after `end` depends on the condition"
+                        .to_string()
+                ),
+                (Severity::Error, "`if` without a matching `end`".to_string()),
+            ],
+            messages("true if 1 end true if")
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    errors,
+    lexer::{ArgumentType, ILToken},
+    runtime,
+};
+
+/// Escapes a string for a literal in both assembly and C, `?` is written in
+/// octal so it can't start a C trigraph
+pub fn escape_string(str: &str) -> String {
+    let mut escaped = String::new();
+    for byte in str.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e if byte != b'?' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Finds every function defined in the program, maps its name to the
+/// index of its `FuncDef` token
+fn collect_functions(tokens: &[ILToken]) -> HashMap<String, usize> {
+    let mut functions = HashMap::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if let ILToken::FuncDef(info) = token {
+            functions.insert(info.name.clone(), i);
+        }
+    }
+    functions
+}
+
+/// Finds every global variable, maps its name to its index in declaration
+/// order and its declared type
+pub fn collect_variables(tokens: &[ILToken]) -> HashMap<&str, (usize, Option<ArgumentType>)> {
+    let mut variables = HashMap::new();
+    for token in tokens {
+        if let ILToken::Var(name, declared) = token {
+            variables.insert(name.as_str(), (variables.len(), *declared));
+        }
+    }
+    variables
+}
+
+/// Lowers the program to x86-64 assembly in GNU `as` syntax. The value stack
+/// and the builtins live in the C runtime, control flow and function calls
+/// are native
+pub fn generate(tokens: &[ILToken]) -> Result<String, errors::CompileError> {
+    let functions = collect_functions(tokens);
+    let variables = collect_variables(tokens);
+    let mut text = String::new();
+    let mut strings = vec![];
+    let mut quotations = vec![];
+
+    writeln!(text, "    .text").unwrap();
+    writeln!(text, "    .globl main").unwrap();
+    writeln!(text, "main:").unwrap();
+    writeln!(text, "    push %rbp").unwrap();
+    writeln!(text, "    mov %rsp, %rbp").unwrap();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            ILToken::PushString(str) => {
+                writeln!(text, "    lea .Lstr{}(%rip), %rdi", strings.len()).unwrap();
+                writeln!(text, "    call iod_push_string").unwrap();
+                strings.push(str.clone());
+            }
+            ILToken::PushUnsignedInteger(num) => {
+                writeln!(text, "    movabs ${}, %rdi", num).unwrap();
+                writeln!(text, "    call iod_push_unsigned_int").unwrap();
+            }
+            ILToken::PushSignedInteger(num) => {
+                writeln!(text, "    movabs ${}, %rdi", num).unwrap();
+                writeln!(text, "    call iod_push_signed_int").unwrap();
+            }
+            ILToken::PushBigInteger(num) => {
+                writeln!(text, "    lea .Lstr{}(%rip), %rdi", strings.len()).unwrap();
+                writeln!(text, "    call iod_push_big_int").unwrap();
+                strings.push(num.to_string());
+            }
+            ILToken::PushFloat(num) => {
+                writeln!(text, "    movabs ${}, %rax", num.to_bits()).unwrap();
+                writeln!(text, "    movq %rax, %xmm0").unwrap();
+                writeln!(text, "    call iod_push_float").unwrap();
+            }
+            ILToken::PushBoolean(num) => {
+                writeln!(text, "    movabs ${}, %rdi", num).unwrap();
+                writeln!(text, "    call iod_push_bool").unwrap();
+            }
+            ILToken::Symbol(name) => {
+                if let Some(symbol) = runtime::word_symbol(name) {
+                    writeln!(text, "    call {}", symbol).unwrap();
+                } else if let Some(pos) = functions.get(name) {
+                    writeln!(text, "    call .Lfn{}", pos).unwrap();
+                } else {
+                    return Err(errors::UnknownWordError { name: name.clone() }.into());
+                }
+            }
+            ILToken::TailCall(name) => {
+                let def = tokens[..i]
+                    .iter()
+                    .rposition(|t| matches!(t, ILToken::FuncDef(_)))
+                    .unwrap_or(0);
+                if matches!(&tokens[def], ILToken::FuncDef(info) if info.locals > 0) {
+                    writeln!(text, "    call iod_frame_exit").unwrap();
+                }
+                writeln!(text, "    add $8, %rsp").unwrap();
+                writeln!(text, "    jmp .Lfn{}", functions[name]).unwrap();
+            }
+            ILToken::If(end) => {
+                writeln!(text, "    call iod_pop_condition").unwrap();
+                writeln!(text, "    test %eax, %eax").unwrap();
+                writeln!(text, "    jz .L{}", end).unwrap();
+            }
+            // NOTE: Test blocks only run in the interpreter's test runner
+            ILToken::Test(_, end) => writeln!(text, "    jmp .L{}", end).unwrap(),
+            ILToken::End => writeln!(text, ".L{}:", i).unwrap(),
+            ILToken::FuncDef(info) => {
+                writeln!(text, "    jmp .Lskip{}", i).unwrap();
+                writeln!(text, "# fdef {}", info).unwrap();
+                writeln!(text, ".Lfn{}:", i).unwrap();
+                // NOTE: Keep the stack 16 byte aligned for calls into the runtime
+                writeln!(text, "    sub $8, %rsp").unwrap();
+                if info.locals > 0 {
+                    writeln!(text, "    mov ${}, %rdi", info.locals).unwrap();
+                    writeln!(text, "    call iod_frame_enter").unwrap();
+                }
+            }
+            ILToken::FuncEnd => {
+                let def = tokens[..i]
+                    .iter()
+                    .rposition(|t| matches!(t, ILToken::FuncDef(_)))
+                    .unwrap_or(0);
+                if matches!(&tokens[def], ILToken::FuncDef(info) if info.locals > 0) {
+                    writeln!(text, "    call iod_frame_exit").unwrap();
+                }
+                writeln!(text, "    add $8, %rsp").unwrap();
+                writeln!(text, "    ret").unwrap();
+                writeln!(text, ".Lskip{}:", def).unwrap();
+            }
+            ILToken::Return => {
+                let def = tokens[..i]
+                    .iter()
+                    .rposition(|t| matches!(t, ILToken::FuncDef(_)))
+                    .unwrap_or(0);
+                if matches!(&tokens[def], ILToken::FuncDef(info) if info.locals > 0) {
+                    writeln!(text, "    call iod_frame_exit").unwrap();
+                }
+                writeln!(text, "    add $8, %rsp").unwrap();
+                writeln!(text, "    ret").unwrap();
+            }
+            ILToken::Let(names, first) => {
+                for slot in (*first..first + names.len()).rev() {
+                    writeln!(text, "    mov ${}, %rdi", slot).unwrap();
+                    writeln!(text, "    call iod_local_set").unwrap();
+                }
+            }
+            ILToken::Local(slot) => {
+                writeln!(text, "    mov ${}, %rdi", slot).unwrap();
+                writeln!(text, "    call iod_local_get").unwrap();
+            }
+            ILToken::Fetch(name) => {
+                let (index, _) = variables[name.as_str()];
+                writeln!(text, "    mov ${}, %rdi", index).unwrap();
+                writeln!(text, "    lea .Lstr{}(%rip), %rsi", strings.len()).unwrap();
+                writeln!(text, "    call iod_variable_fetch").unwrap();
+                strings.push(name.clone());
+            }
+            ILToken::Store(name) => {
+                let (index, declared) = variables[name.as_str()];
+                writeln!(text, "    mov ${}, %rdi", index).unwrap();
+                writeln!(text, "    lea .Lstr{}(%rip), %rsi", strings.len()).unwrap();
+                strings.push(name.clone());
+                match declared {
+                    Some(declared) => {
+                        writeln!(text, "    lea .Lstr{}(%rip), %rdx", strings.len()).unwrap();
+                        strings.push(declared.to_string());
+                    }
+                    None => writeln!(text, "    xor %edx, %edx").unwrap(),
+                }
+                writeln!(text, "    call iod_variable_store").unwrap();
+            }
+            ILToken::Quotation(_) => {
+                writeln!(text, "    jmp .Lskip{}", i).unwrap();
+                writeln!(text, ".Lq{}:", i).unwrap();
+                writeln!(text, "    sub $8, %rsp").unwrap();
+                quotations.push(i);
+            }
+            ILToken::QuotationEnd => {
+                let start = quotations.pop().unwrap_or(0);
+                writeln!(text, "    add $8, %rsp").unwrap();
+                writeln!(text, "    ret").unwrap();
+                writeln!(text, ".Lskip{}:", start).unwrap();
+                writeln!(text, "    lea .Lq{}(%rip), %rdi", start).unwrap();
+                writeln!(text, "    call iod_push_quotation").unwrap();
+            }
+            ILToken::ListStart => writeln!(text, "    call iod_list_start").unwrap(),
+            ILToken::ListEnd => writeln!(text, "    call iod_list_end").unwrap(),
+            ILToken::Comment(_)
+            | ILToken::DocComment(_)
+            | ILToken::Const(..)
+            | ILToken::Var(..) => {}
+        }
+    }
+
+    writeln!(text, "    xor %eax, %eax").unwrap();
+    writeln!(text, "    pop %rbp").unwrap();
+    writeln!(text, "    ret").unwrap();
+
+    writeln!(text, "    .section .rodata").unwrap();
+    for (i, str) in strings.iter().enumerate() {
+        writeln!(text, ".Lstr{}:", i).unwrap();
+        writeln!(text, "    .asciz \"{}\"", escape_string(str)).unwrap();
+    }
+    writeln!(text, "    .section .note.GNU-stack,\"\",@progbits").unwrap();
+
+    Ok(text)
+}
+
+/// Compiles `inputs` together with the runtime into an executable at
+/// `output` using the system C compiler
+pub fn compile_with_runtime(inputs: &[&Path], output: &Path) -> io::Result<()> {
+    let mut cc = Command::new("cc")
+        .arg("-O2")
+        .arg("-o")
+        .arg(output)
+        .args(inputs)
+        .args(["-x", "c", "-", "-lm"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    cc.stdin
+        .take()
+        .unwrap()
+        .write_all(runtime::SOURCE.as_bytes())?;
+
+    let status = cc.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("cc failed with {}", status)));
+    }
+    Ok(())
+}
+
+/// Writes the assembly next to `output` (with a `.s` extension) and links it
+/// into a standalone executable
+pub fn build(tokens: &[ILToken], output: &Path) -> Result<(), String> {
+    let asm = generate(tokens).map_err(|e| e.to_string())?;
+    let asm_path = output.with_extension("s");
+    std::fs::write(&asm_path, asm).map_err(|e| e.to_string())?;
+    compile_with_runtime(&[&asm_path], output).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, test_utils};
+
+    fn build_executable(tokens: &[ILToken], exe: &Path) {
+        build(tokens, exe).unwrap();
+    }
+
+    fn assert_same_output(src: &str, input: &'static str) {
+        test_utils::assert_same_output(build_executable, src, input);
+    }
+
+    #[test]
+    fn every_builtin() {
+        test_utils::assert_every_builtin(build_executable);
+    }
+
+    #[test]
+    fn printing_values() {
+        assert_same_output(
+            "\"Hello??=\" print 42 print -7 print 0.1 0.2 + print 1 3 / print true print 2 3 < print",
+            "",
+        );
+    }
+
+    #[test]
+    fn float_formatting() {
+        assert_same_output(
+            "1 2 / print 100 1 * print 0.000123 print 123456789.125 print -2.5 print 1 0 / print \
+             10000000000.0 10000000000 * 10 * print 0 -1 * print",
+            "",
+        );
+    }
+
+    #[test]
+    fn functions_and_ifs() {
+        assert_same_output(
+            "# Squares # fdef square : number dup * fend \
+             fdef p : ! print fend \
+             5 square p 3 2 > if \"yes\" p end 2 3 > if \"no\" p end",
+            "",
+        );
+    }
+
+    #[test]
+    fn local_variables() {
+        assert_same_output(
+            "fdef fact : number let n in n 1 <= if 1 end n 1 > if n 1 - fact n * end end fend \
+             fdef show : ! let a b in b print a print a 10 * let a in a print end a print end fend \
+             5 fact print 1 2 show",
+            "",
+        );
+    }
+
+    #[test]
+    fn early_return() {
+        assert_same_output(
+            "fdef sign : number dup 0 < if drop -1 return end 0 > if 1 return end 0 fend \
+             fdef first : number let a b in a return end fend \
+             -5 sign print 0 sign print 7 sign print 1 2 first print",
+            "",
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_same_output(
+            "var total number var last \
+             fdef add : ! dup last ! total @ + total ! fend \
+             0 total ! 2 add 3 add total @ print last @ print",
+            "",
+        );
+    }
+
+    #[test]
+    fn quotations_and_lists() {
+        assert_same_output(
+            "{ 1 2 3 } [ dup * ] map print { 1 2 3 4 } [ 2 > ] filter print \
+             { 1 2 3 } 0 [ + ] fold print { \"a\" { true } } print [ 1 ] print \
+             fdef scale : list let n in { 1 2 } [ n * ] map end fend 10 scale print \
+             fdef big : ! { 1 5 9 } [ dup 4 > if print 0 end drop ] each fend \
+             big 3 [ \"hi\" print ] times { 1 } [ 0 exit ] each \"unreachable\" print",
+            "",
+        );
+    }
+
+    #[test]
+    fn tail_calls() {
+        assert_same_output(
+            "fdef count number : ! let n in n 0 > if n 1 - count end end fend \
+             fdef even number : number let n in n 0 == if 1 return end n 1 - odd end fend \
+             fdef odd number : number let n in n 0 == if 0 return end n 1 - even end fend \
+             100000 count 10001 even print 10000 odd print",
+            "",
+        );
+    }
+
+    #[test]
+    fn host_access() {
+        let path = test_utils::temp_path("host-access");
+        let src = format!(
+            "\"contents\" {path:?} write_file {path:?} read_file print \
+             \"IODINE_UNSET_VARIABLE\" env print random 1 < print now 0 > print",
+            path = path.to_string_lossy()
+        );
+        assert_same_output(&src, "");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reading_whole_input() {
+        assert_same_output(
+            "eof? print get_line print eof? print read_all print eof? print",
+            "first\nsecond\n third \n",
+        );
+    }
+
+    #[test]
+    fn input() {
+        assert_same_output(
+            "get_line print get_int print get_uint print get_float print",
+            "Hello there\n -123\n 246\n 2.5\n",
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_same_output(
+            "-2.9 to_int print 3.5 to_uint print \"-12\" to_int print \"+7\" to_uint print \
+             \"2.5e3\" to_float print 7 to_float print 5i print 5u print 5f print \
+             1 2 / to_string print { 1 \"a\" } to_string print 0 to_bool print \"\" to_bool print",
+            "",
+        );
+    }
+
+    #[test]
+    fn logic_and_integer_math() {
+        assert_same_output(
+            "true false and print true false or print true not print 12 10 xor print 5 not print \
+             5 3 shl print -5 1 shr print -7 3 mod print -7 2 divmod print print 5.5 -2 mod print \
+             1.5 -2 divmod print print -9223372036854775808 abs print 3 neg print 3 -2 min print \
+             1 2.5 max print 3 40 pow print 2 -2 pow print 1.5 2.5 pow print 2 sqrt print",
+            "",
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_same_output(
+            "2 2 >= print 1 2 >= print 18446744073709551615 18446744073709551614 > print \
+             9007199254740993 9007199254740992.0 > print -1 0.5 < print \"apple\" \"banana\" < print \
+             false true >= print { 1 \"a\" } { 1 \"b\" } < print { 1 2 } { 1 } > print \
+             { 1 { 2 } } { 1 { 2 } } == print 0 0 / dup != print [ 1 ] dup == print",
+            "",
+        );
+    }
+
+    #[test]
+    fn unknown_word() {
+        let tokens = Lexer::new("1 frobnicate", false).parse();
+        assert!(generate(&tokens).is_err());
+    }
+
+    #[test]
+    fn big_integers() {
+        assert_same_output(
+            "100000000000000000000 print -100000000000000000000 1 - print \
+             18446744073709551615 1 + print 2 100 pow print 3 -40 pow print",
+            "",
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::{
+    capabilities::Capabilities,
+    errors::LexError,
+    interpreter::Interpreter,
+    lexer::{ILToken, Span},
+};
+
+/// Evaluates every `const` definition and replaces the later uses of each
+/// constant with its value. On error returns the position of the failing
+/// definition
+pub fn fold(program: &mut [ILToken]) -> Result<(), (LexError, usize)> {
+    let mut values: HashMap<String, ILToken> = HashMap::new();
+    for (i, token) in program.iter_mut().enumerate() {
+        match token {
+            ILToken::Const(name, body) => {
+                let value = evaluate(name, body, &values).map_err(|e| (e, i))?;
+                values.insert(name.clone(), value);
+            }
+            ILToken::Symbol(name) => {
+                if let Some(value) = values.get(name) {
+                    *token = value.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Runs the expression of the constant `name`, which has to leave exactly one
+/// value that a token can push
+fn evaluate(
+    name: &str,
+    body: &[ILToken],
+    values: &HashMap<String, ILToken>,
+) -> Result<ILToken, LexError> {
+    let body: Vec<ILToken> = body
+        .iter()
+        .map(|token| match token {
+            ILToken::Symbol(word) => values.get(word).unwrap_or(token).clone(),
+            token => token.clone(),
+        })
+        .collect();
+    let spans = vec![Span::default(); body.len()];
+    let mut interpreter =
+        Interpreter::with_capabilities(body, spans, None, None, false, Capabilities::none());
+    if let Err(e) = interpreter.run() {
+        return Err(LexError::Constant(format!(
+            "Could not evaluate the constant `{}`: {}",
+            name, e
+        )));
+    }
+    if interpreter.get_stack().len() != 1 {
+        return Err(LexError::Constant(format!(
+            "The value of the constant `{}` leaves {} values instead of one",
+            name,
+            interpreter.get_stack().len()
+        )));
+    }
+    ILToken::try_from(interpreter.pop_value().unwrap()).map_err(|value| {
+        LexError::Constant(format!(
+            "The value of the constant `{}` is a {}, only numbers, strings and bools can be constants",
+            name,
+            value.argument_type()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_uses_are_replaced() {
+        let mut program = vec![
+            ILToken::Symbol("A".to_string()),
+            ILToken::Const("A".to_string(), vec![ILToken::PushUnsignedInteger(2)]),
+            ILToken::Const(
+                "B".to_string(),
+                vec![
+                    ILToken::Symbol("A".to_string()),
+                    ILToken::Symbol("dup".to_string()),
+                    ILToken::Symbol("*".to_string()),
+                ],
+            ),
+            ILToken::Symbol("A".to_string()),
+            ILToken::Symbol("B".to_string()),
+        ];
+        fold(&mut program).unwrap();
+
+        assert_eq!(ILToken::Symbol("A".to_string()), program[0]);
+        assert_eq!(ILToken::PushUnsignedInteger(2), program[3]);
+        assert_eq!(ILToken::PushUnsignedInteger(4), program[4]);
+    }
+
+    #[test]
+    fn failing_definition() {
+        let mut program = vec![
+            ILToken::PushUnsignedInteger(1),
+            ILToken::Const(
+                "A".to_string(),
+                vec![
+                    ILToken::PushUnsignedInteger(1),
+                    ILToken::PushUnsignedInteger(0),
+                    ILToken::Symbol("/".to_string()),
+                    ILToken::Symbol("to_int".to_string()),
+                ],
+            ),
+        ];
+        let (error, position) = fold(&mut program).unwrap_err();
+
+        assert_eq!(1, position);
+        assert_eq!(
+            "Could not evaluate the constant `A`: `to_int` can't convert inf to a signed integer",
+            error.summary()
+        );
+    }
+}
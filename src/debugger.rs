@@ -0,0 +1,363 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    disasm,
+    errors::RuntimeError,
+    interpreter::{self, Interpreter},
+    lexer::ILToken,
+};
+
+const HELP: &str = "\
+Commands:
+  s, step             Execute a single instruction
+  n, next             Execute a single instruction, stepping over function calls
+  c, continue         Run until a breakpoint is hit or the program ends
+  b, break <target>   Break at a source line number or before a word is executed
+  d, delete <target>  Remove a breakpoint
+  breakpoints         List the breakpoints
+  stack               Print the value stack
+  rstack              Print the return stack
+  bt, backtrace       Print the active function calls
+  l, list             Print the current instruction
+  q, quit             Stop debugging
+  h, help             Print this message
+An empty line repeats the previous command";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Breakpoint {
+    Line(usize),
+    Word(String),
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "line {}", line),
+            Self::Word(word) => write!(f, "word {}", word),
+        }
+    }
+}
+
+impl Breakpoint {
+    fn parse(target: &str) -> Self {
+        match target.parse::<usize>() {
+            Ok(line) => Self::Line(line),
+            Err(_) => Self::Word(target.to_string()),
+        }
+    }
+}
+
+pub struct Debugger {
+    interpreter: Interpreter,
+    commands: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    /// `commands` and `output` are used for the debugger itself, the program
+    /// keeps the input and output of `interpreter`
+    pub fn new(
+        interpreter: Interpreter,
+        commands: Box<dyn BufRead>,
+        output: Box<dyn Write>,
+    ) -> Self {
+        Self {
+            interpreter,
+            commands,
+            output,
+            breakpoints: vec![],
+        }
+    }
+
+    fn line_of(&self, position: usize) -> usize {
+        self.interpreter
+            .spans
+            .get(position)
+            .map_or(0, |span| span.line)
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        let position = self.interpreter.position;
+        let line = self.line_of(position);
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Line(l) => {
+                *l == line && (position == 0 || self.line_of(position - 1) != line)
+            }
+            Breakpoint::Word(word) => {
+                matches!(&self.interpreter.tokens[position], ILToken::Symbol(name) | ILToken::TailCall(name) if name == word)
+            }
+        })
+    }
+
+    fn print_current(&mut self) -> io::Result<()> {
+        if self.interpreter.finished() {
+            return writeln!(self.output, "Program finished");
+        }
+        let position = self.interpreter.position;
+        writeln!(
+            self.output,
+            "{:04}  line {}  {}",
+            position,
+            self.line_of(position),
+            disasm::describe(&self.interpreter.tokens[position])
+        )
+    }
+
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        if !self.interpreter.finished() {
+            self.interpreter.step()?;
+        }
+        Ok(())
+    }
+
+    fn step_over(&mut self) -> Result<(), RuntimeError> {
+        let depth = self.interpreter.get_return_stack().len();
+        self.step()?;
+        while !self.interpreter.finished()
+            && self.interpreter.get_return_stack().len() > depth
+            && !self.at_breakpoint()
+        {
+            self.interpreter.step()?;
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), RuntimeError> {
+        self.step()?;
+        while !self.interpreter.finished() && !self.at_breakpoint() {
+            self.interpreter.step()?;
+        }
+        Ok(())
+    }
+
+    /// Prints a runtime error, the program stays at the failing instruction
+    fn report(&mut self, result: Result<(), RuntimeError>) -> io::Result<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => writeln!(self.output, "Runtime error: {}", e),
+        }
+    }
+
+    fn print_stack(&mut self) -> io::Result<()> {
+        let stack = interpreter::format_stack(self.interpreter.get_stack());
+        writeln!(self.output, "{}", stack)
+    }
+
+    fn print_return_stack(&mut self) -> io::Result<()> {
+        let positions: Vec<String> = self
+            .interpreter
+            .get_return_stack()
+            .iter()
+            .map(|frame| format!("{:04}", frame.return_position))
+            .collect();
+        writeln!(self.output, "[{}]", positions.join(", "))
+    }
+
+    fn print_backtrace(&mut self) -> io::Result<()> {
+        let position = self.interpreter.position;
+        writeln!(
+            self.output,
+            "#0  {:04}  line {}",
+            position,
+            self.line_of(position)
+        )?;
+
+        let calls: Vec<(String, usize)> = self
+            .interpreter
+            .get_return_stack()
+            .iter()
+            .map(|frame| (frame.name.clone(), frame.return_position))
+            .collect();
+        for (i, (name, call)) in calls.iter().rev().enumerate() {
+            writeln!(
+                self.output,
+                "#{}  {} called at {:04}  line {}",
+                i + 1,
+                name,
+                call,
+                self.line_of(*call)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Executes a single command, returns false once the session should end
+    fn execute(&mut self, command: &str) -> io::Result<bool> {
+        let mut parts = command.split_whitespace();
+        let Some(name) = parts.next() else {
+            return Ok(true);
+        };
+        let argument = parts.next();
+
+        match (name, argument) {
+            ("s" | "step", _) => {
+                let result = self.step();
+                self.report(result)?;
+                self.print_current()?;
+            }
+            ("n" | "next", _) => {
+                let result = self.step_over();
+                self.report(result)?;
+                self.print_current()?;
+            }
+            ("c" | "continue", _) => {
+                let result = self.resume();
+                if result.is_ok() && !self.interpreter.finished() {
+                    write!(self.output, "Breakpoint hit: ")?;
+                }
+                self.report(result)?;
+                self.print_current()?;
+            }
+            ("b" | "break", Some(target)) => {
+                let breakpoint = Breakpoint::parse(target);
+                writeln!(self.output, "Breakpoint set at {}", breakpoint)?;
+                self.breakpoints.push(breakpoint);
+            }
+            ("d" | "delete", Some(target)) => {
+                let breakpoint = Breakpoint::parse(target);
+                self.breakpoints.retain(|b| *b != breakpoint);
+            }
+            ("breakpoints", _) => {
+                for breakpoint in &self.breakpoints {
+                    writeln!(self.output, "{}", breakpoint)?;
+                }
+            }
+            ("stack", _) => self.print_stack()?,
+            ("rstack", _) => self.print_return_stack()?,
+            ("bt" | "backtrace", _) => self.print_backtrace()?,
+            ("l" | "list", _) => self.print_current()?,
+            ("q" | "quit", _) => return Ok(false),
+            ("h" | "help", _) => writeln!(self.output, "{}", HELP)?,
+            _ => writeln!(
+                self.output,
+                "Unknown command: {}, try `help`",
+                command.trim()
+            )?,
+        }
+        Ok(true)
+    }
+
+    /// Reads commands until `quit` or the end of the command input
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut previous = String::new();
+        self.print_current()?;
+
+        loop {
+            write!(self.output, "(idb) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if line.trim().is_empty() {
+                line = previous.clone();
+            }
+
+            if !self.execute(&line)? {
+                return Ok(());
+            }
+            previous = line;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, test_utils::SharedBuffer};
+
+    fn debug(src: &str, commands: &'static str) -> String {
+        let interpreter = Interpreter::new(
+            Lexer::new(src, false),
+            Some(Box::new(SharedBuffer::default())),
+            None,
+            false,
+        );
+        let output = SharedBuffer::default();
+        let mut debugger = Debugger::new(
+            interpreter,
+            Box::new(commands.as_bytes()),
+            Box::new(output.clone()),
+        );
+        debugger.run().unwrap();
+        output.contents()
+    }
+
+    const SRC: &str = "fdef square : number\n  dup *\nfend\n\n3 square\n4 square";
+
+    #[test]
+    fn step_into_function() {
+        let expected = "\
+0000  line 1  fdef square : number
+(idb) 0004  line 5  push_uint 3
+(idb) 0005  line 5  word square
+(idb) 0001  line 2  word dup
+(idb) 0002  line 2  word *
+(idb) [3, 3]
+(idb) ";
+
+        assert_eq!(expected, debug(SRC, "s\ns\n\ns\nstack\nq\n"));
+    }
+
+    #[test]
+    fn step_over_function() {
+        let expected = "\
+0000  line 1  fdef square : number
+(idb) 0004  line 5  push_uint 3
+(idb) 0005  line 5  word square
+(idb) 0006  line 6  push_uint 4
+(idb) [9]
+(idb) ";
+
+        assert_eq!(expected, debug(SRC, "n\nn\nn\nstack\nq\n"));
+    }
+
+    #[test]
+    fn breakpoints_and_backtrace() {
+        let expected = "\
+0000  line 1  fdef square : number
+(idb) Breakpoint set at word *
+(idb) Breakpoint hit: 0002  line 2  word *
+(idb) #0  0002  line 2
+#1  square called at 0005  line 5
+(idb) [0005]
+(idb) Breakpoint set at line 6
+(idb) (idb) Breakpoint hit: 0006  line 6  push_uint 4
+(idb) (idb) Program finished
+(idb) ";
+
+        assert_eq!(
+            expected,
+            debug(SRC, "b *\nc\nbt\nrstack\nb 6\nd *\nc\nd 6\nc\n")
+        );
+    }
+
+    #[test]
+    fn backtrace_after_tail_call() {
+        let src =
+            "fdef square : number\n  dup *\nfend\nfdef shifted : number\n  2 + square\nfend\n3 shifted";
+        let expected = "\
+0000  line 1  fdef square : number
+(idb) Breakpoint set at word *
+(idb) Breakpoint hit: 0002  line 2  word *
+(idb) #0  0002  line 2
+#1  square called at 0010  line 7
+(idb) ";
+
+        assert_eq!(expected, debug(src, "b *\nc\nbt\nq\n"));
+    }
+
+    #[test]
+    fn runtime_error() {
+        let expected = "\
+0000  line 1  push_uint 1
+(idb) Runtime error: Empty stack during execution
+0001  line 1  word +
+(idb) []
+(idb) ";
+
+        assert_eq!(expected, debug("1 +", "c\nstack\nq\n"));
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+
+use crate::lexer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the source without running it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    /// Stable identifier of the kind of problem, used to suppress it
+    pub code: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message,
+            code: None,
+        }
+    }
+
+    pub fn warning(span: Span, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message,
+            code: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "line {}: {}", self.span.line, severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
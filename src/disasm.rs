@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+
+use crate::lexer::{ILToken, Span};
+
+/// Textual form of a single instruction
+pub fn describe(token: &ILToken) -> String {
+    match token {
+        ILToken::PushString(str) => format!("push_string {:?}", str),
+        ILToken::PushUnsignedInteger(num) => format!("push_uint {}", num),
+        ILToken::PushSignedInteger(num) => format!("push_int {}", num),
+        ILToken::PushBigInteger(num) => format!("push_bigint {}", num),
+        ILToken::PushFloat(num) => format!("push_float {:?}", num),
+        ILToken::PushBoolean(num) => format!("push_bool {}", num),
+        ILToken::Symbol(name) => format!("word {}", name),
+        ILToken::TailCall(name) => format!("tail_call {}", name),
+        ILToken::If(target) => format!("if -> {:04}", target),
+        ILToken::Test(name, end) => format!("test {:?} -> {:04}", name, end),
+        ILToken::FuncDef(info) => format!("fdef {}", info),
+        ILToken::FuncEnd => "fend".to_string(),
+        ILToken::Return => "return".to_string(),
+        ILToken::Quotation(end) => format!("quotation -> {:04}", end),
+        ILToken::QuotationEnd => "quotation_end".to_string(),
+        ILToken::ListStart => "list_start".to_string(),
+        ILToken::ListEnd => "list_end".to_string(),
+        ILToken::End => "end".to_string(),
+        ILToken::Comment(text) => format!("comment {:?}", text),
+        ILToken::DocComment(text) => format!("doc {:?}", text),
+        ILToken::Const(name, _) => format!("const {}", name),
+        ILToken::Let(names, first) => format!("let {} @{}", names.join(" "), first),
+        ILToken::Local(slot) => format!("local @{}", slot),
+        ILToken::Var(name, Some(declared)) => format!("var {} {}", name, declared),
+        ILToken::Var(name, None) => format!("var {}", name),
+        ILToken::Fetch(name) => format!("fetch {}", name),
+        ILToken::Store(name) => format!("store {}", name),
+    }
+}
+
+/// Writes the program one instruction per line, prefixed with its index and
+/// the source line it came from. Function bodies and `if` blocks are indented
+pub fn disassemble(tokens: &[ILToken], spans: &[Span], out: &mut dyn Write) -> io::Result<()> {
+    let mut depth: usize = 0;
+    let mut separated = true;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let line = spans.get(i).map_or(0, |span| span.line);
+
+        match token {
+            ILToken::FuncEnd | ILToken::End | ILToken::QuotationEnd => {
+                depth = depth.saturating_sub(1)
+            }
+            ILToken::FuncDef(_) if !separated => writeln!(out)?,
+            _ => {}
+        }
+        separated = false;
+
+        writeln!(
+            out,
+            "{:04}  {:>4}  {}{}",
+            i,
+            line,
+            "    ".repeat(depth),
+            describe(token)
+        )?;
+
+        match token {
+            ILToken::FuncDef(_)
+            | ILToken::If(_)
+            | ILToken::Test(..)
+            | ILToken::Let(..)
+            | ILToken::Quotation(_) => depth += 1,
+            ILToken::FuncEnd => {
+                writeln!(out)?;
+                separated = true;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn disassemble_source(src: &str) -> String {
+        let (tokens, spans) = Lexer::new(src, false).parse_with_spans();
+        let mut out = vec![];
+        disassemble(&tokens, &spans, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn jump_targets() {
+        let expected = "\
+0000     1  push_uint 1
+0001     1  if -> 0003
+0002     2      push_string \"one\"
+0003     2  end
+";
+
+        assert_eq!(expected, disassemble_source("1 if\n\"one\" end"));
+    }
+
+    #[test]
+    fn function_boundaries() {
+        let expected = "\
+0000     1  fdef square : number
+0001     2      word dup
+0002     2      word *
+0003     3  fend
+
+0004     4  push_float 2.0
+0005     4  word square
+";
+
+        assert_eq!(
+            expected,
+            disassemble_source("fdef square : number\ndup *\nfend\n2.0 square")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::Write as _;
+
+use crate::{
+    built_in_words::BUILTINS,
+    checker,
+    lexer::{ArgumentType, ILToken, Span},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Reference documentation of a single word
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordDoc {
+    pub name: String,
+    /// Stack effect, e.g. `( number number -- number )`
+    pub signature: String,
+    pub doc: String,
+}
+
+/// Text of doc comments with the indentation of every line removed
+fn clean(comments: &[&str]) -> String {
+    let lines: Vec<&str> = comments
+        .iter()
+        .flat_map(|comment| comment.lines())
+        .map(str::trim)
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Every function defined in the program, in source order, together with the
+/// doc comments right before its `fdef`. Inputs that are not declared in the
+/// header are inferred by the checker when possible
+pub fn functions(tokens: &[ILToken], spans: &[Span]) -> Vec<WordDoc> {
+    let effects = checker::check(tokens, spans).effects;
+    let mut docs = vec![];
+    let mut pending: Vec<&str> = vec![];
+
+    for token in tokens {
+        match token {
+            ILToken::DocComment(text) => pending.push(text),
+            // NOTE: Regular comments between the doc comment and `fdef` are allowed
+            ILToken::Comment(_) => {}
+            ILToken::FuncDef(info) => {
+                let inputs: Vec<String> = if info.inputs.is_empty() {
+                    effects.get(&info.name).map_or(vec![], |effect| {
+                        effect.inputs.iter().map(|t| t.to_string()).collect()
+                    })
+                } else {
+                    info.inputs.iter().map(|t| t.to_string()).collect()
+                };
+                let output = match info.return_type {
+                    ArgumentType::Nothing => String::new(),
+                    t => format!(" {}", t),
+                };
+                let inputs: String = inputs.iter().map(|t| format!(" {}", t)).collect();
+
+                docs.push(WordDoc {
+                    name: info.name.clone(),
+                    signature: format!("({} --{} )", inputs, output),
+                    doc: clean(&pending),
+                });
+                pending.clear();
+            }
+            _ => pending.clear(),
+        }
+    }
+    docs
+}
+
+pub fn builtins() -> Vec<WordDoc> {
+    BUILTINS
+        .iter()
+        .map(|builtin| WordDoc {
+            name: builtin.name.to_string(),
+            signature: builtin.signature.to_string(),
+            doc: builtin.description.to_string(),
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_markdown(title: &str, words: &[WordDoc]) -> String {
+    let mut page = String::new();
+    writeln!(page, "# {}", title).unwrap();
+    for word in words {
+        writeln!(page, "\n## `{}`\n", word.name).unwrap();
+        writeln!(page, "```\n{} {}\n```", word.name, word.signature).unwrap();
+        if !word.doc.is_empty() {
+            writeln!(page, "\n{}", word.doc).unwrap();
+        }
+    }
+    page
+}
+
+fn render_html(title: &str, words: &[WordDoc]) -> String {
+    let mut page = String::new();
+    writeln!(page, "<!DOCTYPE html>").unwrap();
+    writeln!(page, "<html>").unwrap();
+    writeln!(page, "<head>").unwrap();
+    writeln!(page, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(page, "<title>{}</title>", escape_html(title)).unwrap();
+    writeln!(page, "</head>").unwrap();
+    writeln!(page, "<body>").unwrap();
+    writeln!(page, "<h1>{}</h1>", escape_html(title)).unwrap();
+    for word in words {
+        let name = escape_html(&word.name);
+        writeln!(page, "<h2 id=\"{}\"><code>{}</code></h2>", name, name).unwrap();
+        writeln!(
+            page,
+            "<pre><code>{} {}</code></pre>",
+            name,
+            escape_html(&word.signature)
+        )
+        .unwrap();
+        for paragraph in word.doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+            writeln!(page, "<p>{}</p>", escape_html(paragraph.trim())).unwrap();
+        }
+    }
+    writeln!(page, "</body>").unwrap();
+    writeln!(page, "</html>").unwrap();
+    page
+}
+
+/// Renders a reference page listing `words`
+pub fn render(title: &str, words: &[WordDoc], format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => render_markdown(title, words),
+        DocFormat::Html => render_html(title, words),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn docs(src: &str) -> Vec<WordDoc> {
+        let (tokens, spans) = Lexer::new(src, false).parse_with_spans();
+        functions(&tokens, &spans)
+    }
+
+    #[test]
+    fn attaches_doc_comments() {
+        let src = "\
+## Squares a number.
+
+   Works on any number. ##
+fdef square : number
+    dup *
+fend
+
+## Not attached ##
+1 drop
+fdef add number number : number + fend
+## Prints ## # regular # fdef show string : ! print fend";
+
+        assert_eq!(
+            vec![
+                WordDoc {
+                    name: "square".to_string(),
+                    signature: "( any -- number )".to_string(),
+                    doc: "Squares a number.\n\nWorks on any number.".to_string(),
+                },
+                WordDoc {
+                    name: "add".to_string(),
+                    signature: "( number number -- number )".to_string(),
+                    doc: String::new(),
+                },
+                WordDoc {
+                    name: "show".to_string(),
+                    signature: "( string -- )".to_string(),
+                    doc: "Prints".to_string(),
+                },
+            ],
+            docs(src)
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let expected = "\
+# main
+
+## `square`
+
+```
+square ( any -- number )
+```
+
+Squares a number
+";
+
+        let words = docs("## Squares a number ## fdef square : number dup * fend");
+        assert_eq!(expected, render("main", &words, DocFormat::Markdown));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        let page = render("builtins", &builtins(), DocFormat::Html);
+
+        assert!(page.contains("<h2 id=\"&lt;=\"><code>&lt;=</code></h2>"));
+        assert!(page.contains("<pre><code>dup ( a -- a a )</code></pre>"));
+    }
+}
@@ -0,0 +1,395 @@
+use std::{collections::HashMap, fmt::Write as _};
+
+use crate::{codegen, errors, lexer::ILToken, runtime};
+
+fn float_literal(num: f64) -> String {
+    if num.is_nan() {
+        "NAN".to_string()
+    } else if num.is_infinite() {
+        if num < 0.0 { "-INFINITY" } else { "INFINITY" }.to_string()
+    } else {
+        format!("{:e}", num)
+    }
+}
+
+/// Runs functions through a trampoline: a tail call stores the function to
+/// run next in `iod_tail` and returns, so chains of tail calls between
+/// functions don't grow the C stack
+const TRAMPOLINE: &str = "\
+static void (*iod_tail)(void);
+
+static void iod_call(void (*function)(void)) {
+    while (function) {
+        iod_tail = NULL;
+        function();
+        function = iod_tail;
+    }
+}
+";
+
+/// Translates the program into a standalone C file. The runtime is included
+/// verbatim, each `fdef` and each quotation becomes a C function and the top
+/// level code becomes `main`
+pub fn emit(tokens: &[ILToken]) -> Result<String, errors::CompileError> {
+    let mut functions: HashMap<&str, usize> = HashMap::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if let ILToken::FuncDef(info) = token {
+            functions.insert(&info.name, i);
+        }
+    }
+
+    let variables = codegen::collect_variables(tokens);
+
+    let mut declarations = String::new();
+    let mut bodies = String::new();
+    let mut main = String::new();
+    // NOTE: Quotations can nest, each one is written to its own buffer until
+    // its `]` together with the block depth to go back to
+    let mut quotations: Vec<(usize, String, usize)> = vec![];
+    let mut quotation_bodies = String::new();
+    let mut in_function = false;
+    let mut has_locals = false;
+    let mut function = "";
+    let mut depth = 1;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let out = match quotations.last_mut() {
+            Some((_, body, _)) => body,
+            None if in_function => &mut bodies,
+            None => &mut main,
+        };
+        let indent = "    ".repeat(depth);
+        match token {
+            ILToken::PushString(str) => writeln!(
+                out,
+                "{}iod_push_string(\"{}\");",
+                indent,
+                codegen::escape_string(str)
+            )
+            .unwrap(),
+            ILToken::PushUnsignedInteger(num) => {
+                writeln!(out, "{}iod_push_unsigned_int({}ULL);", indent, num).unwrap()
+            }
+            // NOTE: `-9223372036854775808LL` is not a valid literal in C
+            ILToken::PushSignedInteger(i64::MIN) => writeln!(
+                out,
+                "{}iod_push_signed_int(-9223372036854775807LL - 1);",
+                indent
+            )
+            .unwrap(),
+            ILToken::PushSignedInteger(num) => {
+                writeln!(out, "{}iod_push_signed_int({}LL);", indent, num).unwrap()
+            }
+            ILToken::PushBigInteger(num) => {
+                writeln!(out, "{}iod_push_big_int(\"{}\");", indent, num).unwrap()
+            }
+            ILToken::PushFloat(num) => {
+                writeln!(out, "{}iod_push_float({});", indent, float_literal(*num)).unwrap()
+            }
+            ILToken::PushBoolean(num) => {
+                writeln!(out, "{}iod_push_bool({});", indent, num).unwrap()
+            }
+            ILToken::Symbol(name) => {
+                if let Some(symbol) = runtime::word_symbol(name) {
+                    writeln!(out, "{}{}();", indent, symbol).unwrap();
+                } else if let Some(pos) = functions.get(name.as_str()) {
+                    writeln!(out, "{}iod_call(iod_fn{});", indent, pos).unwrap();
+                } else {
+                    return Err(errors::UnknownWordError { name: name.clone() }.into());
+                }
+            }
+            ILToken::TailCall(name) if name == function => {
+                writeln!(out, "{}goto tail;", indent).unwrap()
+            }
+            ILToken::TailCall(name) => {
+                if has_locals {
+                    writeln!(out, "{}iod_frame_exit();", indent).unwrap();
+                }
+                writeln!(
+                    out,
+                    "{}iod_tail = iod_fn{};",
+                    indent,
+                    functions[name.as_str()]
+                )
+                .unwrap();
+                writeln!(out, "{}return;", indent).unwrap();
+            }
+            ILToken::If(_) => {
+                writeln!(out, "{}if (iod_pop_condition()) {{", indent).unwrap();
+                depth += 1;
+            }
+            // NOTE: Test blocks only run in the interpreter's test runner
+            ILToken::Test(name, _) => {
+                writeln!(out, "{}if (0) {{ /* test {:?} */", indent, name).unwrap();
+                depth += 1;
+            }
+            ILToken::End => {
+                depth -= 1;
+                writeln!(out, "{}}}", "    ".repeat(depth)).unwrap();
+            }
+            ILToken::FuncDef(info) => {
+                writeln!(declarations, "static void iod_fn{}(void);", i).unwrap();
+                writeln!(bodies, "/* fdef {} */", info).unwrap();
+                writeln!(bodies, "static void iod_fn{}(void) {{", i).unwrap();
+                if info.locals > 0 {
+                    writeln!(bodies, "    iod_frame_enter({});", info.locals).unwrap();
+                }
+                // NOTE: Self tail calls jump back to the start as C compilers
+                // only turn other calls into jumps when optimizing
+                let mut body = tokens[i..].iter().take_while(|t| **t != ILToken::FuncEnd);
+                if body.any(|t| matches!(t, ILToken::TailCall(name) if *name == info.name)) {
+                    writeln!(bodies, "tail:").unwrap();
+                }
+                in_function = true;
+                has_locals = info.locals > 0;
+                function = &info.name;
+            }
+            ILToken::FuncEnd => {
+                if has_locals {
+                    writeln!(bodies, "    iod_frame_exit();").unwrap();
+                }
+                writeln!(bodies, "}}\n").unwrap();
+                in_function = false;
+            }
+            ILToken::Return => {
+                if has_locals {
+                    writeln!(out, "{}iod_frame_exit();", indent).unwrap();
+                }
+                writeln!(out, "{}return;", indent).unwrap();
+            }
+            ILToken::Let(names, first) => {
+                writeln!(out, "{}{{ /* let {} */", indent, names.join(" ")).unwrap();
+                for slot in (*first..first + names.len()).rev() {
+                    writeln!(out, "{}    iod_local_set({});", indent, slot).unwrap();
+                }
+                depth += 1;
+            }
+            ILToken::Local(slot) => writeln!(out, "{}iod_local_get({});", indent, slot).unwrap(),
+            ILToken::Fetch(name) => {
+                let (index, _) = variables[name.as_str()];
+                writeln!(
+                    out,
+                    "{}iod_variable_fetch({}, \"{}\");",
+                    indent,
+                    index,
+                    codegen::escape_string(name)
+                )
+                .unwrap()
+            }
+            ILToken::Store(name) => {
+                let (index, declared) = variables[name.as_str()];
+                let declared = match declared {
+                    Some(declared) => format!("\"{}\"", declared),
+                    None => "NULL".to_string(),
+                };
+                writeln!(
+                    out,
+                    "{}iod_variable_store({}, \"{}\", {});",
+                    indent,
+                    index,
+                    codegen::escape_string(name),
+                    declared
+                )
+                .unwrap()
+            }
+            ILToken::Quotation(_) => {
+                writeln!(declarations, "static void iod_q{}(void);", i).unwrap();
+                quotations.push((i, format!("static void iod_q{}(void) {{\n", i), depth));
+                depth = 1;
+            }
+            ILToken::QuotationEnd => {
+                let Some((start, body, outer_depth)) = quotations.pop() else {
+                    continue;
+                };
+                quotation_bodies.push_str(&body);
+                writeln!(quotation_bodies, "}}\n").unwrap();
+                depth = outer_depth;
+
+                let out = match quotations.last_mut() {
+                    Some((_, body, _)) => body,
+                    None if in_function => &mut bodies,
+                    None => &mut main,
+                };
+                writeln!(
+                    out,
+                    "{}iod_push_quotation(iod_q{});",
+                    "    ".repeat(depth),
+                    start
+                )
+                .unwrap();
+            }
+            ILToken::ListStart => writeln!(out, "{}iod_list_start();", indent).unwrap(),
+            ILToken::ListEnd => writeln!(out, "{}iod_list_end();", indent).unwrap(),
+            ILToken::Comment(_)
+            | ILToken::DocComment(_)
+            | ILToken::Const(..)
+            | ILToken::Var(..) => {}
+        }
+    }
+
+    let mut c = String::new();
+    writeln!(c, "{}", runtime::SOURCE).unwrap();
+    writeln!(c, "/* Generated from Iodine source */\n").unwrap();
+    writeln!(c, "{}", TRAMPOLINE).unwrap();
+    writeln!(c, "{}", declarations).unwrap();
+    write!(c, "{}", quotation_bodies).unwrap();
+    write!(c, "{}", bodies).unwrap();
+    writeln!(c, "int main(void) {{").unwrap();
+    write!(c, "{}", main).unwrap();
+    writeln!(c, "    return 0;").unwrap();
+    writeln!(c, "}}").unwrap();
+
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::{path::Path, process::Command};
+
+    fn build_executable(tokens: &[ILToken], exe: &Path) {
+        let c_file = exe.with_extension("c");
+        std::fs::write(&c_file, emit(tokens).unwrap()).unwrap();
+
+        let status = Command::new("cc")
+            .arg("-o")
+            .arg(exe)
+            .arg(&c_file)
+            .arg("-lm")
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    fn assert_same_output(src: &str, input: &'static str) {
+        test_utils::assert_same_output(build_executable, src, input);
+    }
+
+    #[test]
+    fn every_builtin() {
+        test_utils::assert_every_builtin(build_executable);
+    }
+
+    #[test]
+    fn builtins() {
+        assert_same_output(
+            "\"Who?? \\\\ me??=\" print 9 dup * print 7 -2 - print 1 2 / print 0.1 0.2 + print \
+             -9223372036854775808 print 18446744073709551615 print \
+             1 2 < print 1 2 > print 2 2 <= print 2 2 == print 2 2 != print 1 2 drop print",
+            "",
+        );
+    }
+
+    #[test]
+    fn swap_and_exit() {
+        assert_same_output(
+            "fdef stop : ! \"stopping\" print 0 exit fend \
+             1 2 swap print print stop \"unreachable\" print",
+            "",
+        );
+    }
+
+    #[test]
+    fn functions_and_ifs() {
+        assert_same_output(
+            "fdef square : number dup * fend \
+             fdef check : ! 10 > if \"big\" print end fend \
+             # Nested blocks # 4 square dup print check 3 check \
+             1 if 1 if \"nested\" print end end",
+            "",
+        );
+    }
+
+    #[test]
+    fn local_variables() {
+        assert_same_output(
+            "fdef fib : number let n in n 2 < if n end n 1 > if n 1 - fib n 2 - fib + end end fend \
+             fdef sub : number let a b in a b - end fend \
+             10 fib print 10 3 sub print",
+            "",
+        );
+    }
+
+    #[test]
+    fn early_return() {
+        assert_same_output(
+            "fdef clamp : number let n in n 0 < if 0 return end n 10 > if 10 return end n end fend \
+             -3 clamp print 4 clamp print 12 clamp print",
+            "",
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_same_output(
+            "var count number var greeting \
+             \"hi\" greeting ! 1 count ! \
+             fdef bump : ! count @ 1 + count ! fend \
+             bump bump count @ print greeting @ print",
+            "",
+        );
+    }
+
+    #[test]
+    fn quotations_and_lists() {
+        assert_same_output(
+            "{ 1 2 3 } [ dup * ] map print { 1 2 3 4 } [ 2 > ] filter print \
+             { \"x\" [ ] } print { } print \
+             fdef total list : number 0 [ + ] fold fend { 1 2 3 } total print \
+             fdef adder : quotation let n in [ n + ] end fend 1 5 adder call print \
+             2 [ [ \"inner\" print ] call ] times",
+            "",
+        );
+    }
+
+    const PARITY: &str = "fdef count number : ! let n in n 0 > if n 1 - count end end fend \
+         fdef even number : number let n in n 0 == if 1 return end n 1 - odd end fend \
+         fdef odd number : number let n in n 0 == if 0 return end n 1 - even end fend ";
+
+    #[test]
+    fn tail_calls() {
+        assert_same_output(
+            &format!("{}100000 count 10001 even print 10000 odd print", PARITY),
+            "",
+        );
+        // NOTE: Too slow for the interpreter in a debug build, the deep calls
+        // only have to run in constant stack
+        let deep = format!(
+            "{}3000000 count 3000000 even print 3000001 odd print",
+            PARITY
+        );
+        assert_eq!(
+            "1\n1\n",
+            test_utils::compile_and_run(build_executable, &deep, "")
+        );
+    }
+
+    #[test]
+    fn host_access() {
+        let path = test_utils::temp_path("host-access");
+        let src = format!(
+            "\"contents\" {path:?} write_file {path:?} read_file print \
+             \"IODINE_UNSET_VARIABLE\" env print random 1 < print now 0 > print",
+            path = path.to_string_lossy()
+        );
+        assert_same_output(&src, "");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reading_whole_input() {
+        assert_same_output(
+            "eof? print get_line print eof? print read_all print eof? print",
+            "first\nsecond\n third \n",
+        );
+    }
+
+    #[test]
+    fn input() {
+        assert_same_output(
+            "get_line print get_int print get_uint print get_float print",
+            "Hello there\n -123\n 246\n 2.5\n",
+        );
+    }
+}
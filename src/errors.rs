@@ -5,6 +5,16 @@ pub enum NumberParseErrorType {
     WrongFloat,
     NonNumericChar,
     OnlyNegativeSign,
+    /// Digit that is too large for the base, like `2` in `0b102`
+    InvalidDigit,
+    /// `_` that is not between two digits
+    MisplacedSeparator,
+    /// `e` not followed by the digits of the exponent
+    WrongExponent,
+    /// `0x`, `0b` or `0o` without digits
+    MissingDigits,
+    /// `i` or `u` after a float, like `2.5i`
+    WrongSuffix,
 }
 
 #[derive(Debug, Clone)]
@@ -26,17 +36,85 @@ impl NumberParseError {
 
 #[derive(Debug, Clone)]
 pub struct UnterminatedStringError;
-#[derive(Debug)]
+
+/// Any error that stops the lexer
+#[derive(Debug, Clone)]
+pub enum LexError {
+    Number(NumberParseError),
+    UnterminatedString(UnterminatedStringError),
+    /// A literal that can't be represented, e.g. because it overflows
+    Literal(String),
+    /// `fdef` that is not followed by `name : type`
+    FunctionHeader(String),
+    /// `test` that is not followed by a string
+    TestHeader(String),
+    /// `const` definition that is malformed or can't be evaluated
+    Constant(String),
+    /// `let` that is malformed or outside of a function
+    Let(String),
+    /// `var` that is malformed, or a variable not followed by `@` or `!`
+    Variable(String),
+    /// `return` outside of a function or inside a quotation
+    Return(String),
+    /// Block, quotation or list that is never closed, or an `end`, `]` or
+    /// `}` that closes nothing
+    Unbalanced(String),
+}
+
+impl LexError {
+    /// Single line description of the error
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Number(e) => format!("{}{}", e.message(), e.literal),
+            Self::UnterminatedString(e) => e.to_string().trim_end().to_string(),
+            Self::Literal(message)
+            | Self::FunctionHeader(message)
+            | Self::TestHeader(message)
+            | Self::Constant(message)
+            | Self::Let(message)
+            | Self::Variable(message)
+            | Self::Return(message)
+            | Self::Unbalanced(message) => message.clone(),
+        }
+    }
+}
+
+impl From<NumberParseError> for LexError {
+    fn from(value: NumberParseError) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<UnterminatedStringError> for LexError {
+    fn from(value: UnterminatedStringError) -> Self {
+        Self::UnterminatedString(value)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
 pub struct EmptyStackError;
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError;
 
-impl fmt::Display for NumberParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = match self.error_type {
+impl NumberParseError {
+    fn message(&self) -> &'static str {
+        match self.error_type {
             NumberParseErrorType::WrongFloat => "Too many decimal points found in number: ",
             NumberParseErrorType::NonNumericChar => "Non-numeric char found in number: ",
             NumberParseErrorType::OnlyNegativeSign => "Only negative sign found in number",
-        };
-        writeln!(f, "{}", message).unwrap();
+            NumberParseErrorType::InvalidDigit => "Digit too large for the base of number: ",
+            NumberParseErrorType::MisplacedSeparator => {
+                "Separator not between two digits in number: "
+            }
+            NumberParseErrorType::WrongExponent => "Exponent without digits in number: ",
+            NumberParseErrorType::MissingDigits => "No digits after the base of number: ",
+            NumberParseErrorType::WrongSuffix => "Integer suffix found in float: ",
+        }
+    }
+}
+
+impl fmt::Display for NumberParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message()).unwrap();
         write!(f, "\t").unwrap();
         for _ in 0..self.pos {
             write!(f, " ").unwrap();
@@ -53,8 +131,179 @@ impl fmt::Display for UnterminatedStringError {
     }
 }
 
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(e) => write!(f, "{}", e),
+            Self::UnterminatedString(e) => write!(f, "{}", e),
+            Self::Literal(message)
+            | Self::FunctionHeader(message)
+            | Self::TestHeader(message)
+            | Self::Constant(message)
+            | Self::Let(message)
+            | Self::Variable(message)
+            | Self::Return(message)
+            | Self::Unbalanced(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Could not tokenize the whole file, refusing to format it"
+        )
+    }
+}
+
 impl fmt::Display for EmptyStackError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Empty stack during execution")
+        write!(f, "Empty stack during execution")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownWordError {
+    pub name: String,
+}
+
+impl fmt::Display for UnknownWordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown word: {}", self.name)
+    }
+}
+
+/// Reasons a program can't be compiled to native code or C
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    UnknownWord(UnknownWordError),
+}
+
+impl From<UnknownWordError> for CompileError {
+    fn from(value: UnknownWordError) -> Self {
+        Self::UnknownWord(value)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownWord(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Any error that stops the interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    EmptyStack(EmptyStackError),
+    UnknownWord(UnknownWordError),
+    /// `assert` or `assert_eq` failed
+    Assertion(String),
+    /// Variable read before anything was stored in it, or given a value of
+    /// the wrong type
+    Variable(String),
+    /// Word given a value of a type it can't work with
+    Type(String),
+    /// Value that has no equivalent in the type a conversion word converts to
+    Conversion(String),
+    /// Integer division by zero, or a shift by a count out of range
+    Arithmetic(String),
+    Limit(LimitError),
+    /// Word needs a capability the interpreter was not given
+    Permission(String),
+    /// Reading or writing a file failed
+    Io(String),
+    Input(InputError),
+}
+
+impl From<EmptyStackError> for RuntimeError {
+    fn from(value: EmptyStackError) -> Self {
+        Self::EmptyStack(value)
+    }
+}
+
+impl From<InputError> for RuntimeError {
+    fn from(value: InputError) -> Self {
+        Self::Input(value)
+    }
+}
+
+impl From<LimitError> for RuntimeError {
+    fn from(value: LimitError) -> Self {
+        Self::Limit(value)
+    }
+}
+
+impl From<UnknownWordError> for RuntimeError {
+    fn from(value: UnknownWordError) -> Self {
+        Self::UnknownWord(value)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyStack(e) => write!(f, "{}", e),
+            Self::UnknownWord(e) => write!(f, "{}", e),
+            Self::Assertion(message) => write!(f, "Assertion failed: {}", message),
+            Self::Variable(message)
+            | Self::Type(message)
+            | Self::Conversion(message)
+            | Self::Arithmetic(message)
+            | Self::Permission(message)
+            | Self::Io(message) => write!(f, "{}", message),
+            Self::Limit(e) => write!(f, "{}", e),
+            Self::Input(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// An input word could not read a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputError {
+    /// Nothing is left to read
+    Eof,
+    /// The line read is not a value of the expected kind
+    Malformed {
+        expected: &'static str,
+        line: String,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eof => write!(f, "Reached the end of the input"),
+            Self::Malformed { expected, line } => {
+                write!(f, "Expected {} but got `{}`", expected, line)
+            }
+        }
+    }
+}
+
+/// A resource limit set on the interpreter was exceeded, holds the limit
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitError {
+    Instructions(u64),
+    StackDepth(usize),
+    CallDepth(usize),
+    /// Length of a string in bytes, of a list or of an integer in digits
+    ValueSize(usize),
+    Deadline(std::time::Duration),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Instructions(limit) => write!(f, "Executed more than {} instructions", limit),
+            Self::StackDepth(limit) => write!(f, "Stack grew deeper than {} values", limit),
+            Self::CallDepth(limit) => write!(f, "Calls nested deeper than {}", limit),
+            Self::ValueSize(limit) => {
+                write!(f, "String, list or integer grew longer than {}", limit)
+            }
+            Self::Deadline(limit) => write!(f, "Program ran longer than {:?}", limit),
+        }
     }
 }
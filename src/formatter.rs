@@ -0,0 +1,177 @@
+use crate::{
+    errors::FormatError,
+    lexer::{ILToken, Lexer, Span},
+};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, Default)]
+struct Line {
+    depth: usize,
+    words: Vec<String>,
+    /// Source line the first word came from
+    first: usize,
+    /// Source line the last word ended on
+    last: usize,
+}
+
+fn text(token: &ILToken, span: &Span, source: &str) -> String {
+    match token {
+        ILToken::FuncDef(info) => format!("fdef {}", info),
+        ILToken::Comment(comment) if comment.is_empty() => "# #".to_string(),
+        ILToken::Comment(comment) => format!("# {} #", comment),
+        ILToken::DocComment(comment) if comment.is_empty() => "## ##".to_string(),
+        ILToken::DocComment(comment) => format!("## {} ##", comment),
+        ILToken::Const(..) | ILToken::Var(..) | ILToken::Fetch(_) | ILToken::Store(_) => source
+            [span.start..span.end]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => source[span.start..span.end].to_string(),
+    }
+}
+
+/// Reformats the source into the canonical style: one space between words,
+/// four spaces of indentation per `fdef`/`if`/`test` block, `fdef` headers and
+/// `fend` on their own lines and at most one blank line in a row. Line
+/// breaks and comments are otherwise kept where they were
+pub fn format(source: &str) -> Result<String, FormatError> {
+    let mut lexer = Lexer::new(source, false);
+    let (tokens, spans) = lexer.tokenize();
+    if lexer.error().is_some() {
+        return Err(FormatError);
+    }
+
+    let mut lines: Vec<Line> = vec![];
+    let mut depth: usize = 0;
+    let mut force_break = true;
+
+    for (token, span) in tokens.iter().zip(&spans) {
+        let last = span.line + source[span.start..span.end].matches('\n').count();
+        let closes = matches!(token, ILToken::End | ILToken::FuncEnd);
+        let own_line = matches!(token, ILToken::FuncDef(_) | ILToken::FuncEnd);
+        if closes {
+            depth = depth.saturating_sub(1);
+        }
+
+        let continues = lines
+            .last()
+            .is_some_and(|line| line.last == span.line && !force_break && !own_line);
+        if continues {
+            let line = lines.last_mut().unwrap();
+            line.words.push(text(token, span, source));
+            line.last = last;
+        } else {
+            lines.push(Line {
+                depth,
+                words: vec![text(token, span, source)],
+                first: span.line,
+                last,
+            });
+        }
+
+        force_break = own_line;
+        if matches!(
+            token,
+            ILToken::FuncDef(_) | ILToken::If(_) | ILToken::Test(..) | ILToken::Let(..)
+        ) {
+            depth += 1;
+        }
+    }
+
+    let mut formatted = String::new();
+    let mut previous_line: Option<usize> = None;
+    for line in lines {
+        if previous_line.is_some_and(|previous| line.first > previous + 1) {
+            formatted.push('\n');
+        }
+        formatted.push_str(&INDENT.repeat(line.depth));
+        formatted.push_str(&line.words.join(" "));
+        formatted.push('\n');
+        previous_line = Some(line.last);
+    }
+
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indentation() {
+        let src = "fdef   abs   :   number\ndup 0 < if\n-1 *\n  end\n        fend";
+        let expected = "\
+fdef abs : number
+    dup 0 < if
+        -1 *
+    end
+fend
+";
+
+        assert_eq!(expected, format(src).unwrap());
+    }
+
+    #[test]
+    fn splits_function_headers() {
+        let src = "fdef square : number dup * fend 2 square";
+        let expected = "\
+fdef square : number
+    dup *
+fend
+2 square
+";
+
+        assert_eq!(expected, format(src).unwrap());
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let src = "\n\n#   Prints   #\n\n\n\n'a'   print  # multi\n    line #\n\n";
+        let expected = "\
+# Prints #
+
+'a' print # multi
+    line #
+";
+
+        assert_eq!(expected, format(src).unwrap());
+    }
+
+    #[test]
+    fn doc_comments_and_inputs() {
+        let src = "##   Adds   ##   fdef add  number number :  number + fend";
+        let expected = "\
+## Adds ##
+fdef add number number : number
+    +
+fend
+";
+
+        assert_eq!(expected, format(src).unwrap());
+    }
+
+    #[test]
+    fn constants() {
+        let src = "const   SIZE  4\n  1024 *   end\nSIZE print";
+        let expected = "\
+const SIZE 4 1024 * end
+SIZE print
+";
+
+        assert_eq!(expected, format(src).unwrap());
+    }
+
+    #[test]
+    fn idempotent() {
+        let src = std::fs::read_to_string("main.iod").unwrap();
+        let formatted = format(&src).unwrap();
+
+        assert_eq!(formatted, format(&formatted).unwrap());
+    }
+
+    #[test]
+    fn refuses_invalid_source() {
+        assert_eq!(Err(FormatError), format("1 2 6942O 3"));
+    }
+}
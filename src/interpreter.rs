@@ -1,14 +1,18 @@
 use std::{
     collections::HashMap,
-    fmt::Result,
     io::{BufRead, BufReader, Write},
-    process::exit,
+    time::{Duration, Instant},
 };
 
 use crate::{
+    bigint::BigInt,
     built_in_words::*,
-    lexer::{self, FunctionInfo, ILToken},
+    capabilities::{Capabilities, Capability},
+    errors::{EmptyStackError, LimitError, RuntimeError, UnknownWordError},
+    lexer::{self, ArgumentType, FunctionInfo, ILToken, Span},
+    profiler::Profiler,
     stack,
+    trace::Tracer,
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -16,8 +20,21 @@ pub enum StackValue {
     String(String),
     UnsignedInt(u64),
     SignedInt(i64),
+    /// Integer that fits neither `u64` nor `i64`
+    BigInt(BigInt),
     Float(f64),
     Bool(bool),
+    List(Vec<StackValue>),
+    Quotation(Quotation),
+}
+
+/// Code pushed by `[ ... ]`, with the local variables of the function call
+/// it was pushed in
+#[derive(Debug, PartialEq, Clone)]
+pub struct Quotation {
+    /// Position of the `[` token
+    pub start: usize,
+    pub locals: Vec<StackValue>,
 }
 
 impl Default for StackValue {
@@ -44,6 +61,28 @@ impl From<i64> for StackValue {
     }
 }
 
+impl From<i128> for StackValue {
+    /// Smallest integer variant that holds the value
+    fn from(value: i128) -> Self {
+        if let Ok(int) = u64::try_from(value) {
+            Self::UnsignedInt(int)
+        } else if let Ok(int) = i64::try_from(value) {
+            Self::SignedInt(int)
+        } else {
+            Self::BigInt(value.into())
+        }
+    }
+}
+
+impl From<BigInt> for StackValue {
+    fn from(value: BigInt) -> Self {
+        match value.to_i128() {
+            Some(int) => int.into(),
+            None => Self::BigInt(value),
+        }
+    }
+}
+
 impl From<f64> for StackValue {
     fn from(value: f64) -> Self {
         Self::Float(value)
@@ -56,11 +95,30 @@ impl From<bool> for StackValue {
     }
 }
 
+/// Token pushing the value, lists and quotations have no such token and are
+/// given back
+impl TryFrom<StackValue> for ILToken {
+    type Error = StackValue;
+
+    fn try_from(value: StackValue) -> Result<Self, Self::Error> {
+        Ok(match value {
+            StackValue::String(str) => Self::PushString(str),
+            StackValue::UnsignedInt(int) => Self::PushUnsignedInteger(int),
+            StackValue::SignedInt(int) => Self::PushSignedInteger(int),
+            StackValue::BigInt(int) => Self::PushBigInteger(int),
+            StackValue::Float(flt) => Self::PushFloat(flt),
+            StackValue::Bool(bool) => Self::PushBoolean(bool as u64),
+            value @ (StackValue::List(_) | StackValue::Quotation(_)) => return Err(value),
+        })
+    }
+}
+
 impl From<StackValue> for f64 {
     fn from(val: StackValue) -> Self {
         match val {
             StackValue::Float(f) => f,
             StackValue::SignedInt(f) => f as f64,
+            StackValue::BigInt(f) => f.to_f64(),
             StackValue::UnsignedInt(f) => f as f64,
             _ => unreachable!(),
         }
@@ -73,60 +131,292 @@ impl From<StackValue> for bool {
             StackValue::Bool(bool) => bool,
             StackValue::UnsignedInt(int) => int != 0,
             StackValue::SignedInt(int) => int != 0,
+            // NOTE: Big integers are never zero
+            StackValue::BigInt(_) => true,
             StackValue::Float(flt) => flt != 0.0,
             StackValue::String(str) => !str.is_empty(),
+            StackValue::List(items) => !items.is_empty(),
+            StackValue::Quotation(_) => true,
         }
     }
 }
 
+impl StackValue {
+    /// Type of the value as written in function headers
+    pub fn argument_type(&self) -> ArgumentType {
+        match self {
+            Self::String(_) => ArgumentType::String,
+            Self::UnsignedInt(_) | Self::SignedInt(_) | Self::BigInt(_) | Self::Float(_) => {
+                ArgumentType::Number
+            }
+            Self::Bool(_) => ArgumentType::Bool,
+            Self::List(_) => ArgumentType::List,
+            Self::Quotation(_) => ArgumentType::Quotation,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::UnsignedInt(_) => "uint",
+            Self::SignedInt(_) => "int",
+            Self::BigInt(_) => "bigint",
+            Self::Float(_) => "float",
+            Self::Bool(_) => "bool",
+            Self::List(_) => "list",
+            Self::Quotation(_) => "quotation",
+        }
+    }
+
+    /// Same as `Display`, but strings are quoted
+    pub fn repr(&self) -> String {
+        match self {
+            Self::String(str) => format!("{:?}", str),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Formats the stack bottom to top, strings are quoted
+pub fn format_stack(stack: &stack::Stack<StackValue>) -> String {
+    let values: Vec<String> = stack.iter().map(StackValue::repr).collect();
+    format!("[{}]", values.join(", "))
+}
+
 impl std::fmt::Display for StackValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             Self::String(str) => write!(f, "{}", str),
             Self::UnsignedInt(num) => write!(f, "{}", num),
             Self::SignedInt(num) => write!(f, "{}", num),
+            Self::BigInt(num) => write!(f, "{}", num),
             Self::Float(num) => write!(f, "{}", num),
             Self::Bool(bool) => write!(f, "{}", bool),
+            Self::List(items) => {
+                write!(f, "{{")?;
+                for item in items {
+                    write!(f, " {}", item.repr())?;
+                }
+                write!(f, " }}")
+            }
+            Self::Quotation(_) => write!(f, "[ quotation ]"),
         }
     }
 }
 
+/// Call of a function or a quotation
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    /// Position of the call to return to
+    pub return_position: usize,
+    /// Function running in the frame, a tail call replaces it
+    pub name: String,
+    pub locals: Vec<StackValue>,
+    pub quotation: bool,
+}
+
+/// Bounds on the resources a program can use, `None` means unbounded
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    pub stack_depth: Option<usize>,
+    /// Depth of the return stack, also counts running quotations
+    pub call_depth: Option<usize>,
+    /// Length of a string in bytes, of a list or of an integer in digits
+    pub value_size: Option<usize>,
+    /// Wall clock time since the first instruction
+    pub deadline: Option<Duration>,
+}
+
+/// Global variable declared with `var`
+struct Variable {
+    declared: Option<ArgumentType>,
+    value: Option<StackValue>,
+}
+
 pub struct Interpreter {
     pub tokens: Vec<ILToken>,
+    pub spans: Vec<Span>,
     pub position: usize,
     pub output: Box<dyn Write>,
     pub input: Box<dyn BufRead>,
     stack: stack::Stack<StackValue>,
-    return_stack: stack::Stack<usize>,
+    return_stack: stack::Stack<Frame>,
+    /// Stack depths at every open `{`
+    list_starts: stack::Stack<usize>,
     functions: HashMap<String, FunctionInfo>,
-    builtins: HashMap<String, BuiltInAction>,
+    variables: HashMap<String, Variable>,
+    builtins: HashMap<&'static str, &'static Builtin>,
+    capabilities: Capabilities,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    /// Positions of the `test` token and its `end` when running a single test
+    test: Option<(usize, usize)>,
+    exit_code: Option<i32>,
+    /// Line of the `exit` that stopped the program
+    exit_line: usize,
+    limits: Limits,
+    /// Instructions executed so far
+    instructions: u64,
+    /// Time of the first instruction, only taken with a deadline
+    started: Option<Instant>,
     time: bool,
 }
 
-type BuiltInAction = fn(&mut Interpreter);
+pub type BuiltInAction = fn(&mut Interpreter) -> Result<(), RuntimeError>;
 
 impl<'a> Interpreter {
     pub fn new(
-        lexer: lexer::Lexer<'a>,
+        mut lexer: lexer::Lexer<'a>,
         output: Option<Box<dyn Write>>,
         input: Option<Box<dyn BufRead>>,
         time: bool,
     ) -> Self {
-        Self {
-            tokens: lexer.parse(),
+        let (tokens, spans) = lexer.parse_with_spans();
+        Self::with_tokens(tokens, spans, output, input, time)
+    }
+
+    /// Interpreter for an already parsed program
+    pub fn with_tokens(
+        tokens: Vec<ILToken>,
+        spans: Vec<Span>,
+        output: Option<Box<dyn Write>>,
+        input: Option<Box<dyn BufRead>>,
+        time: bool,
+    ) -> Self {
+        Self::with_capabilities(tokens, spans, output, input, time, Capabilities::all())
+    }
+
+    /// Same as `with_tokens`, but builtin words needing anything outside of
+    /// `capabilities` fail with `RuntimeError::Permission`. Stdin and stdout
+    /// are only the default input and output when allowed
+    pub fn with_capabilities(
+        tokens: Vec<ILToken>,
+        spans: Vec<Span>,
+        output: Option<Box<dyn Write>>,
+        input: Option<Box<dyn BufRead>>,
+        time: bool,
+        capabilities: Capabilities,
+    ) -> Self {
+        let output = output.unwrap_or_else(|| -> Box<dyn Write> {
+            if capabilities.allows(Capability::Stdout) {
+                Box::new(std::io::stdout())
+            } else {
+                Box::new(std::io::sink())
+            }
+        });
+        let input = input.unwrap_or_else(|| -> Box<dyn BufRead> {
+            if capabilities.allows(Capability::Stdin) {
+                Box::new(BufReader::new(std::io::stdin()))
+            } else {
+                Box::new(std::io::empty())
+            }
+        });
+        let mut interpreter = Self {
+            tokens,
+            spans,
             position: 0,
-            output: output.unwrap_or_else(|| Box::new(std::io::stdout())),
-            input: input.unwrap_or_else(|| Box::new(BufReader::new(std::io::stdin()))),
+            output,
+            input,
             stack: stack::Stack::new(),
             return_stack: stack::Stack::new(),
+            list_starts: stack::Stack::new(),
             functions: HashMap::new(),
+            variables: HashMap::new(),
             builtins: HashMap::new(),
+            capabilities,
+            tracer: None,
+            profiler: None,
+            test: None,
+            exit_code: None,
+            exit_line: 0,
+            limits: Limits::default(),
+            instructions: 0,
+            started: None,
             time,
+        };
+        interpreter.add_builtins();
+        interpreter.declare_variables();
+        interpreter
+    }
+
+    fn add_builtins(&mut self) {
+        for builtin in BUILTINS {
+            self.builtins.insert(builtin.name, builtin);
+        }
+    }
+
+    /// Variables exist from the start, also when only a test is run
+    fn declare_variables(&mut self) {
+        for token in &self.tokens {
+            if let ILToken::Var(name, declared) = token {
+                self.variables.insert(
+                    name.clone(),
+                    Variable {
+                        declared: *declared,
+                        value: None,
+                    },
+                );
+            }
         }
     }
 
-    fn add_word(&mut self, name: String, func: BuiltInAction) {
-        self.builtins.insert(name, func);
+    /// Records every executed token from now on
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Collects per function and per builtin statistics from now on
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Stops the program with a `RuntimeError::Limit` once it goes past any
+    /// of `limits`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Runs only the function definitions and the test block starting at
+    /// `position` instead of the program
+    pub fn select_test(&mut self, position: usize) {
+        if let Some(ILToken::Test(_, end)) = self.tokens.get(position) {
+            self.test = Some((position, *end));
+        }
+    }
+
+    /// Stops the program after the current token
+    pub fn exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
+        self.exit_line = self.current_line();
+        self.position = self.tokens.len();
+    }
+
+    /// Code passed to `exit`, if the program called it
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Line of the `exit` that stopped the program
+    pub fn exit_line(&self) -> usize {
+        self.exit_line
+    }
+
+    /// Source line of the token at the current position
+    pub fn current_line(&self) -> usize {
+        self.spans.get(self.position).map_or(0, |span| span.line)
     }
 
     fn skip_function_body(&mut self) {
@@ -140,72 +430,259 @@ impl<'a> Interpreter {
         }
     }
 
-    fn interpret(&mut self) -> Result {
+    /// Whether every token of the program has been executed
+    pub fn finished(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    /// Executes the token at the current position and moves past it. On error
+    /// the position is left at the failing token
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
         use lexer::ILToken;
-        while self.position < self.tokens.len() {
-            let token = self.tokens[self.position].clone();
-            match token {
-                ILToken::PushString(value) => self.push_value(value.into()),
-                ILToken::PushUnsignedInteger(value) => self.push_value(value.into()),
-                ILToken::PushSignedInteger(value) => self.push_value(value.into()),
-                ILToken::PushFloat(value) => self.push_value(value.into()),
-                ILToken::PushBoolean(value) => self.push_value(value.into()),
-                ILToken::If(_) => {
-                    word_if(self);
+        let position = self.position;
+        let token = self.tokens[self.position].clone();
+
+        if let Some((start, end)) = self.test {
+            if position == end {
+                self.position = self.tokens.len();
+                return Ok(());
+            }
+            let inside = !self.return_stack.is_empty() || (start..end).contains(&position);
+            if !inside && !matches!(token, ILToken::FuncDef(_)) {
+                self.position += 1;
+                return Ok(());
+            }
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.instruction();
+        }
+        self.check_budget()?;
+
+        match token {
+            ILToken::PushString(value) => self.push_value(value.into()),
+            ILToken::PushUnsignedInteger(value) => self.push_value(value.into()),
+            ILToken::PushSignedInteger(value) => self.push_value(value.into()),
+            ILToken::PushBigInteger(value) => self.push_value(StackValue::BigInt(value)),
+            ILToken::PushFloat(value) => self.push_value(value.into()),
+            ILToken::PushBoolean(value) => self.push_value((value != 0).into()),
+            ILToken::If(_) => word_if(self)?,
+            ILToken::Test(_, end) => {
+                // NOTE: Test blocks only run when selected by the test runner
+                if self.test.is_none_or(|(start, _)| start != position) {
+                    self.position = end;
                 }
-                ILToken::End => {}
-                ILToken::Symbol(name) => match self.builtins.get(&name) {
-                    Some(t) => (t)(self),
-                    None => match self.functions.get(&name) {
-                        Some(info) => {
-                            self.return_stack.push(self.position);
-                            self.position = info.pos;
-                        }
-                        None => {
-                            writeln!(self.output, "Unknown word: {}", name).unwrap();
-                            exit(1)
-                        }
-                    },
-                },
-                ILToken::FuncDef(mut info) => {
-                    info.pos = self.position;
-                    self.functions.insert(info.name.clone(), info);
-                    self.skip_function_body();
+            }
+            ILToken::End => {}
+            ILToken::Let(names, first) => {
+                let mut values = vec![];
+                for _ in &names {
+                    values.push(self.pop_value()?);
                 }
-                ILToken::FuncEnd => {
-                    self.position = self.return_stack.pop().unwrap();
+                // NOTE: The lexer only allows `let` inside functions
+                let frame = self.return_stack.top_mut().unwrap();
+                if frame.locals.len() < first + values.len() {
+                    frame
+                        .locals
+                        .resize(first + values.len(), StackValue::default());
                 }
-                ILToken::CommentMarker => {
-                    self.skip_until(ILToken::CommentMarker);
+                for (slot, value) in (first..).zip(values.into_iter().rev()) {
+                    frame.locals[slot] = value;
                 }
             }
-            self.position += 1;
+            ILToken::Local(slot) => {
+                let value = self.return_stack.top_mut().unwrap().locals[slot].clone();
+                self.push_value(value);
+            }
+            ILToken::Symbol(name) => self.call_word(name, false)?,
+            ILToken::TailCall(name) => self.call_word(name, true)?,
+            ILToken::FuncDef(mut info) => {
+                info.pos = self.position;
+                self.functions.insert(info.name.clone(), info);
+                self.skip_function_body();
+            }
+            ILToken::FuncEnd | ILToken::Return | ILToken::QuotationEnd => {
+                let frame = self.return_stack.pop().unwrap();
+                self.position = frame.return_position;
+                if let (Some(profiler), false) = (&mut self.profiler, frame.quotation) {
+                    profiler.exit();
+                }
+            }
+            ILToken::Quotation(end) => {
+                let locals = self
+                    .return_stack
+                    .top_mut()
+                    .map_or(vec![], |frame| frame.locals.clone());
+                self.push_value(StackValue::Quotation(Quotation {
+                    start: position,
+                    locals,
+                }));
+                self.position = end;
+            }
+            ILToken::ListStart => self.list_starts.push(self.stack.len()),
+            ILToken::ListEnd => {
+                let start = self.list_starts.pop().unwrap_or(0);
+                let mut items = vec![];
+                while self.stack.len() > start {
+                    items.push(self.pop_value()?);
+                }
+                items.reverse();
+                self.push_value(StackValue::List(items));
+            }
+            ILToken::Fetch(name) => {
+                let variable = &self.variables[&name];
+                let Some(value) = variable.value.clone() else {
+                    return Err(RuntimeError::Variable(format!(
+                        "Variable `{}` was read before anything was stored in it",
+                        name
+                    )));
+                };
+                self.push_value(value);
+            }
+            ILToken::Store(name) => {
+                let value = self.pop_value()?;
+                let variable = self.variables.get_mut(&name).unwrap();
+                if let Some(declared) = variable.declared {
+                    if value.argument_type() != declared {
+                        let error = RuntimeError::Variable(format!(
+                            "Variable `{}` holds {} but got {}",
+                            name,
+                            declared,
+                            value.argument_type()
+                        ));
+                        self.push_value(value);
+                        return Err(error);
+                    }
+                }
+                variable.value = Some(value);
+            }
+            ILToken::Comment(_)
+            | ILToken::DocComment(_)
+            | ILToken::Const(..)
+            | ILToken::Var(..) => {}
+        }
+        self.check_sizes()?;
+        self.position += 1;
+
+        if let Some(tracer) = &mut self.tracer {
+            let line = self.spans.get(position).map_or(0, |span| span.line);
+            tracer.record(position, line, &self.tokens[position], &self.stack);
         }
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result {
-        self.add_word("drop".to_string(), word_drop);
-        self.add_word("dup".to_string(), word_dup);
-        self.add_word("print".to_string(), word_print);
-        self.add_word("get_line".to_string(), word_get_line);
+    /// Counts the instruction about to run against the instruction limit and
+    /// checks the deadline
+    fn check_budget(&mut self) -> Result<(), LimitError> {
+        self.instructions += 1;
+        if let Some(limit) = self.limits.instructions {
+            if self.instructions > limit {
+                return Err(LimitError::Instructions(limit));
+            }
+        }
+        self.check_deadline()
+    }
+
+    /// Checks the deadline, called by builtins that can run for a long time
+    pub fn check_deadline(&mut self) -> Result<(), LimitError> {
+        if let Some(limit) = self.limits.deadline {
+            if self.started.get_or_insert_with(Instant::now).elapsed() > limit {
+                return Err(LimitError::Deadline(limit));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the size of a value before a builtin builds it
+    pub fn check_value_size(&self, size: usize) -> Result<(), LimitError> {
+        match self.limits.value_size {
+            Some(limit) if size > limit => Err(LimitError::ValueSize(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the stacks after an instruction. Only the top value can be new,
+    /// so it is the only one whose size is checked
+    fn check_sizes(&self) -> Result<(), LimitError> {
+        if let Some(limit) = self.limits.stack_depth {
+            if self.stack.len() > limit {
+                return Err(LimitError::StackDepth(limit));
+            }
+        }
+        if let Some(limit) = self.limits.call_depth {
+            if self.return_stack.len() > limit {
+                return Err(LimitError::CallDepth(limit));
+            }
+        }
+        let size = match self.stack.top() {
+            Some(StackValue::String(str)) => str.len(),
+            Some(StackValue::List(items)) => items.len(),
+            Some(StackValue::BigInt(int)) => int.digits(),
+            _ => 0,
+        };
+        self.check_value_size(size)
+    }
 
-        self.add_word("+".to_string(), word_add);
-        self.add_word("-".to_string(), word_subtract);
-        self.add_word("/".to_string(), word_divide);
-        self.add_word("*".to_string(), word_multiply);
+    /// Runs a builtin or enters a function. A tail call replaces the frame of
+    /// the running function, so it returns straight to that function's caller
+    fn call_word(&mut self, name: String, tail: bool) -> Result<(), RuntimeError> {
+        if let Some(builtin) = self.builtins.get(name.as_str()).copied() {
+            if let Some(capability) = builtin.capability {
+                if !self.capabilities.allows(capability) {
+                    return Err(RuntimeError::Permission(format!(
+                        "`{}` needs the {} capability",
+                        name, capability
+                    )));
+                }
+            }
+            let start = self.profiler.is_some().then(std::time::Instant::now);
+            (builtin.action)(self)?;
+            if let (Some(profiler), Some(start)) = (&mut self.profiler, start) {
+                profiler.builtin(&name, start.elapsed());
+            }
+            return Ok(());
+        }
 
-        self.add_word("<".to_string(), word_less);
-        self.add_word(">".to_string(), word_more);
-        self.add_word("<=".to_string(), word_less_or_equal);
-        self.add_word(">=".to_string(), word_more_or_equal);
-        self.add_word("==".to_string(), word_equal);
-        self.add_word("!=".to_string(), word_not_equal);
+        let Some(info) = self.functions.get(&name) else {
+            return Err(UnknownWordError { name }.into());
+        };
+        let locals = vec![StackValue::default(); info.locals];
+        let pos = info.pos;
+        match self.return_stack.top_mut() {
+            Some(frame) if tail => {
+                frame.name = name.clone();
+                frame.locals = locals;
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.exit();
+                }
+            }
+            _ => self.return_stack.push(Frame {
+                return_position: self.position,
+                name: name.clone(),
+                locals,
+                quotation: false,
+            }),
+        }
+        self.position = pos;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(&name);
+        }
+        Ok(())
+    }
 
-        self.add_word("get_int".to_string(), word_get_int);
-        self.add_word("get_uint".to_string(), word_get_uint);
-        self.add_word("get_float".to_string(), word_get_float);
+    fn interpret(&mut self) -> Result<(), RuntimeError> {
+        let mut result = Ok(());
+        while result.is_ok() && !self.finished() {
+            result = self.step();
+        }
+        // NOTE: A failing run is still reported, so its open frames are closed too
+        if let Some(profiler) = &mut self.profiler {
+            profiler.finish();
+        }
+        result
+    }
 
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
         if self.time {
             let now = std::time::Instant::now();
             let result = self.interpret();
@@ -217,6 +694,29 @@ impl<'a> Interpreter {
         self.interpret()
     }
 
+    /// Runs `quotation` to completion, called by the words taking one. Checks
+    /// the deadline first as those words can call it many times
+    pub fn call_quotation(&mut self, quotation: Quotation) -> Result<(), RuntimeError> {
+        self.check_deadline()?;
+        let depth = self.return_stack.len();
+        let position = self.position;
+        self.return_stack.push(Frame {
+            return_position: position,
+            name: "[ quotation ]".to_string(),
+            locals: quotation.locals,
+            quotation: true,
+        });
+        self.position = quotation.start + 1;
+        while self.return_stack.len() > depth && self.exit_code.is_none() {
+            self.step()?;
+        }
+        // NOTE: Stay at the end of the program if the quotation called `exit`
+        if self.exit_code.is_none() {
+            self.position = position;
+        }
+        Ok(())
+    }
+
     pub fn push_value(&mut self, value: StackValue) {
         match value {
             StackValue::String(str) => self.stack.push(StackValue::String(str.to_string())),
@@ -224,22 +724,35 @@ impl<'a> Interpreter {
         }
     }
 
-    pub fn pop_value(&mut self) -> Option<StackValue> {
-        self.stack.pop()
+    pub fn pop_value(&mut self) -> Result<StackValue, EmptyStackError> {
+        self.stack.pop().ok_or(EmptyStackError)
     }
 
-    #[cfg(test)]
     pub fn get_stack(&self) -> &stack::Stack<StackValue> {
         &self.stack
     }
+
+    /// Every active function call, innermost last
+    pub fn get_return_stack(&self) -> &stack::Stack<Frame> {
+        &self.return_stack
+    }
 }
 
 #[cfg(test)]
 mod tests {
     // TODO: Commandline output tests
-    use crate::{interpreter::Interpreter, lexer::Lexer, stack::Stack};
+    use crate::{
+        bigint::BigInt,
+        capabilities::{Capabilities, Capability},
+        errors::{InputError, LimitError, RuntimeError},
+        interpreter::Interpreter,
+        lexer::Lexer,
+        stack::Stack,
+        test_utils,
+    };
 
-    use super::StackValue;
+    use super::{Limits, StackValue};
+    use std::time::Duration;
 
     #[test]
     fn empty_program() {
@@ -288,9 +801,10 @@ mod tests {
         interpreter.run().unwrap();
 
         let mut expected_stack: Stack<StackValue> = Stack::new();
-        for _ in 0..4 {
-            expected_stack.push(StackValue::Float(2.0));
+        for _ in 0..3 {
+            expected_stack.push(StackValue::UnsignedInt(2));
         }
+        expected_stack.push(StackValue::Float(2.0));
 
         assert_eq!(&expected_stack, interpreter.get_stack());
     }
@@ -346,6 +860,53 @@ mod tests {
         assert_eq!(&expected_stack, interpreter.get_stack());
     }
 
+    #[test]
+    fn input_errors() {
+        let run = |src, input: &'static str| {
+            let lexer = Lexer::new(src, false);
+            let mut interpreter =
+                Interpreter::new(lexer, None, Some(Box::new(input.as_bytes())), false);
+            interpreter.run().unwrap_err()
+        };
+
+        assert_eq!(
+            RuntimeError::Input(InputError::Malformed {
+                expected: "a signed integer",
+                line: "12a".to_string()
+            }),
+            run("get_int", " 12a\n")
+        );
+        assert_eq!(
+            RuntimeError::Input(InputError::Malformed {
+                expected: "an unsigned integer",
+                line: "-5".to_string()
+            }),
+            run("get_uint", "-5\n")
+        );
+        assert_eq!(
+            RuntimeError::Input(InputError::Eof),
+            run("get_line get_float", "only line")
+        );
+    }
+
+    #[test]
+    fn reading_whole_input() {
+        let src = "var count 0 count ! \
+                   fdef lines : ! eof? if return end get_line drop count @ 1 + count ! lines fend \
+                   lines count @ eof? read_all";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter =
+            Interpreter::new(lexer, None, Some(Box::new("a\nb\n\nc".as_bytes())), false);
+        interpreter.run().unwrap();
+
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push(4_u64.into());
+        expected_stack.push(true.into());
+        expected_stack.push(String::new().into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+    }
+
     #[test]
     fn if_statement() {
         let src = "18 15 > if \"You are underage\" end";
@@ -359,6 +920,44 @@ mod tests {
         assert_eq!(&expected_stack, interpreter.get_stack());
     }
 
+    #[test]
+    fn big_integers() {
+        let src = "18446744073709551615 1 + \
+                   -9223372036854775808 1 - \
+                   100000000000000000000 99999999999999999999 - \
+                   fdef fact number : number let n in n 1 <= if 1 return end n 1 - fact n * end fend \
+                   30 fact 2 /";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        interpreter.run().unwrap();
+
+        let big = |str| StackValue::BigInt(BigInt::parse(str).unwrap());
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push(big("18446744073709551616"));
+        expected_stack.push(big("-9223372036854775809"));
+        expected_stack.push(1_u64.into());
+        expected_stack.push(1.3262642990609553e32.into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+    }
+
+    #[test]
+    fn arithmetic_type_errors() {
+        let run = |src| {
+            let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+            interpreter.run().unwrap_err().to_string()
+        };
+
+        assert_eq!("`+` expects number but got string", run("\"a\" 1 +"));
+        assert_eq!("`-` expects number but got bool", run("1 true -"));
+        assert_eq!("`*` expects number but got list", run("{ 1 } 2 *"));
+        assert_eq!("`/` expects number but got list", run("{ 1 } 0 /"));
+        assert_eq!(
+            "`times` expects number but got string",
+            run("\"a\" [ 1 ] times")
+        );
+    }
+
     #[test]
     fn function() {
         let src = "fdef square : number dup * fend 2 square";
@@ -367,11 +966,455 @@ mod tests {
         interpreter.run().unwrap();
 
         let mut expected_stack: Stack<StackValue> = Stack::new();
-        expected_stack.push(4.0.into());
+        expected_stack.push(4_u64.into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+    }
+
+    #[test]
+    fn local_variables() {
+        let src = "fdef sum : number let n in n 0 > if n 1 - sum n + end n 0 == if 0 end end fend \
+                   fdef pair : ! let a b in b a end fend \
+                   3 sum 1 2 pair";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        interpreter.run().unwrap();
+
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push(6_u64.into());
+        expected_stack.push(2_u64.into());
+        expected_stack.push(1_u64.into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+        assert!(interpreter.get_return_stack().is_empty());
+    }
+
+    #[test]
+    fn variables() {
+        let src = "var total number var name \
+                   fdef add : ! total @ + total ! fend \
+                   0 total ! 2 add 3 add total @ \"x\" name ! name @";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        interpreter.run().unwrap();
+
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push(5_u64.into());
+        expected_stack.push("x".to_owned().into());
 
         assert_eq!(&expected_stack, interpreter.get_stack());
     }
 
+    #[test]
+    fn variable_errors() {
+        let run = |src| {
+            let lexer = Lexer::new(src, false);
+            let mut interpreter = Interpreter::new(lexer, None, None, false);
+            interpreter.run().unwrap_err().to_string()
+        };
+
+        assert_eq!(
+            "Variable `x` was read before anything was stored in it",
+            run("var x x @")
+        );
+        assert_eq!(
+            "Variable `x` holds number but got string",
+            run("var x number \"a\" x !")
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        let run = |src| {
+            let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+            interpreter.run().map_err(|e| e.to_string())?;
+            Ok::<_, String>((interpreter.exit_code(), interpreter.exit_line()))
+        };
+
+        assert_eq!(Ok((Some(3), 2)), run("1\n3 exit 4"));
+        assert_eq!(Ok((None, 0)), run("1"));
+        assert_eq!(
+            Err("`exit` expects number but got bool".to_string()),
+            run("true exit")
+        );
+        assert_eq!(
+            Err("`exit` expects number but got string".to_string()),
+            run("\"a\" exit")
+        );
+        assert_eq!(
+            Err("`exit` can't convert 2.7 to a 32 bit exit code".to_string()),
+            run("2.7 exit")
+        );
+        assert_eq!(
+            Err("`exit` can't convert 2147483648 to a 32 bit exit code".to_string()),
+            run("2147483648 exit")
+        );
+        assert_eq!(Ok((Some(-1), 1)), run("-1 exit"));
+    }
+
+    #[test]
+    fn early_return() {
+        let src = "fdef sign : number dup 0 < if drop -1 return end 0 > if 1 return end 0 fend \
+                   -5 sign 0 sign 7 sign";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        interpreter.run().unwrap();
+
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push((-1_i64).into());
+        expected_stack.push(0_u64.into());
+        expected_stack.push(1_u64.into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+    }
+
+    #[test]
+    fn tail_calls() {
+        let src = "fdef count number : ! let n in n 0 > if n 1 - count end end fend \
+                   fdef even number : number let n in n 0 == if 1 return end n 1 - odd end fend \
+                   fdef odd number : number let n in n 0 == if 0 return end n 1 - even end fend \
+                   2000000 count 1001 even";
+        let lexer = Lexer::new(src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        let mut depth = 0;
+        while !interpreter.finished() {
+            interpreter.step().unwrap();
+            depth = depth.max(interpreter.get_return_stack().len());
+        }
+
+        let mut expected_stack: Stack<StackValue> = Stack::new();
+        expected_stack.push(0_u64.into());
+
+        assert_eq!(&expected_stack, interpreter.get_stack());
+        assert_eq!(1, depth);
+    }
+
+    #[test]
+    fn limits() {
+        let run = |src, limits| {
+            let lexer = Lexer::new(src, false);
+            let mut interpreter = Interpreter::new(lexer, None, None, false);
+            interpreter.set_limits(limits);
+            interpreter.run().map_err(|e| match e {
+                RuntimeError::Limit(e) => e,
+                e => panic!("{}", e),
+            })
+        };
+        let forever = "fdef loop : ! loop fend loop";
+
+        assert_eq!(
+            Err(LimitError::Instructions(100)),
+            run(
+                forever,
+                Limits {
+                    instructions: Some(100),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::Deadline(Duration::from_millis(10))),
+            run(
+                forever,
+                Limits {
+                    deadline: Some(Duration::from_millis(10)),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::StackDepth(3)),
+            run(
+                "1 2 3 4",
+                Limits {
+                    stack_depth: Some(3),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::CallDepth(50)),
+            run(
+                "fdef deep : ! deep 1 fend deep",
+                Limits {
+                    call_depth: Some(50),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::ValueSize(2)),
+            run(
+                "{ 1 2 } [ ] map { 1 2 3 }",
+                Limits {
+                    value_size: Some(2),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::ValueSize(4)),
+            run(
+                "\"abcde\"",
+                Limits {
+                    value_size: Some(4),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::ValueSize(30)),
+            run(
+                "4294967296 dup * dup *",
+                Limits {
+                    value_size: Some(30),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::ValueSize(1000)),
+            run(
+                "2 3000000000 pow",
+                Limits {
+                    value_size: Some(1000),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::Deadline(Duration::from_millis(10))),
+            run(
+                "3 200000 pow",
+                Limits {
+                    deadline: Some(Duration::from_millis(10)),
+                    ..Limits::default()
+                }
+            )
+        );
+        assert_eq!(
+            Err(LimitError::Deadline(Duration::from_millis(10))),
+            run(
+                "1000000000000 [ ] times",
+                Limits {
+                    deadline: Some(Duration::from_millis(10)),
+                    ..Limits::default()
+                }
+            )
+        );
+
+        let mut interpreter = Interpreter::new(
+            Lexer::new("read_all", false),
+            None,
+            Some(Box::new("abcde".as_bytes())),
+            false,
+        );
+        interpreter.set_limits(Limits {
+            value_size: Some(4),
+            ..Limits::default()
+        });
+        assert_eq!(
+            Err(RuntimeError::Limit(LimitError::ValueSize(4))),
+            interpreter.run()
+        );
+        assert_eq!(
+            Ok(()),
+            run(
+                "fdef loop number : ! let n in n 0 > if n 1 - loop end end fend 1000 loop",
+                Limits {
+                    stack_depth: Some(5),
+                    call_depth: Some(1),
+                    ..Limits::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        let run = |src: &str, capabilities| {
+            let (tokens, spans) = Lexer::new(src, false).parse_with_spans();
+            let mut interpreter =
+                Interpreter::with_capabilities(tokens, spans, None, None, false, capabilities);
+            interpreter.run().map_err(|e| e.to_string())
+        };
+
+        assert_eq!(Ok(()), run("1 2 + drop", Capabilities::none()));
+        assert_eq!(
+            Err("`print` needs the stdout capability".to_string()),
+            run("1 print", Capabilities::none())
+        );
+        assert_eq!(
+            Err("`get_line` needs the stdin capability".to_string()),
+            run("get_line", Capabilities::none().with(Capability::Stdout))
+        );
+        assert_eq!(
+            Err("`read_file` needs the fs-read capability".to_string()),
+            run("\"/etc/hosts\" read_file", Capabilities::none())
+        );
+        assert_eq!(
+            Err("`random` needs the random capability".to_string()),
+            run(
+                "now drop random",
+                Capabilities::none().with(Capability::Clock)
+            )
+        );
+    }
+
+    #[test]
+    fn host_access() {
+        let path = test_utils::temp_path("host-access");
+        let src = format!(
+            "{{ 1 \"a\" }} {path:?} write_file {path:?} read_file \
+             \"IODINE_UNSET_VARIABLE\" env random now",
+            path = path.to_string_lossy()
+        );
+        let lexer = Lexer::new(&src, false);
+        let mut interpreter = Interpreter::new(lexer, None, None, false);
+        interpreter.run().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let values: Vec<StackValue> = interpreter.get_stack().iter().cloned().collect();
+        assert_eq!(StackValue::String("{ 1 \"a\" }".to_string()), values[0]);
+        assert_eq!(StackValue::String(String::new()), values[1]);
+        assert!(matches!(values[2], StackValue::Float(n) if (0.0..1.0).contains(&n)));
+        assert!(matches!(values[3], StackValue::Float(n) if n > 1.7e9));
+    }
+
+    #[test]
+    fn conversions() {
+        let run = |src: &str| {
+            let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+            interpreter.run().map_err(|e| e.to_string())?;
+            Ok::<_, String>(interpreter.get_stack().iter().cloned().collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            Ok(vec![
+                StackValue::SignedInt(5),
+                StackValue::SignedInt(-2),
+                StackValue::UnsignedInt(42),
+                StackValue::Float(1.0),
+                StackValue::Float(1e20),
+                StackValue::String("{ 1 \"a\" }".to_string()),
+                StackValue::Bool(false),
+                StackValue::Bool(true),
+            ]),
+            run("5 to_int -2.9 to_int \"42\" to_uint true to_float \
+                 100000000000000000000 to_float { 1 \"a\" } to_string 0 to_bool \"false\" to_bool")
+        );
+        assert_eq!(
+            Err("`to_uint` can't convert -1 to an unsigned integer".to_string()),
+            run("-1 to_uint")
+        );
+        assert_eq!(
+            Err("`to_int` can't convert 18446744073709551615 to a signed integer".to_string()),
+            run("18446744073709551615 to_int")
+        );
+        assert_eq!(
+            Err("`to_int` can't convert NaN to a signed integer".to_string()),
+            run("0 0 / to_int")
+        );
+        assert_eq!(
+            Err("`to_float` can't convert \"abc\" to a floating point number".to_string()),
+            run("\"abc\" to_float")
+        );
+        assert_eq!(
+            Err("`to_int` expects number, string or bool but got list".to_string()),
+            run("{ } to_int")
+        );
+    }
+
+    #[test]
+    fn logic_and_integer_math() {
+        let run = |src: &str| {
+            let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+            interpreter.run().map_err(|e| e.to_string())?;
+            Ok::<_, String>(super::format_stack(interpreter.get_stack()))
+        };
+
+        assert_eq!(
+            Ok("[false, true, true, false]".to_string()),
+            run("true false and true false or true true xor not false not not")
+        );
+        assert_eq!(
+            Ok("[8, 14, 6, -6, 40, -3]".to_string()),
+            run("12 10 and 12 10 or 12 10 xor 5 not 5 3 shl -5 1 shr")
+        );
+        assert_eq!(
+            Ok("[-1, -3, -1, 1.5, -0, 1.5]".to_string()),
+            run("-7 3 mod -7 2 divmod 5.5 -2 mod 1.5 -2 divmod")
+        );
+        assert_eq!(
+            Ok("[9223372036854775808, -18446744073709551615, -2, 2.5, \
+                1267650600228229401496703205376, 0.25, 1.5]"
+                .to_string()),
+            run(
+                "-9223372036854775808 abs 18446744073709551615 neg 3 -2 min 1 2.5 max \
+                 2 100 pow 2 -2 pow 2.25 sqrt"
+            )
+        );
+        assert_eq!(
+            Err("`and` expects bool but got number".to_string()),
+            run("true 1 and")
+        );
+        assert_eq!(
+            Err("`xor` expects integers but got number".to_string()),
+            run("1.5 1 xor")
+        );
+        assert_eq!(
+            Err("`shl` can't shift by 64, the count must be between 0 and 63".to_string()),
+            run("1 64 shl")
+        );
+        assert_eq!(Err("`mod` divided by zero".to_string()), run("1 0 mod"));
+        assert_eq!(
+            Err("`pow` result would have more than 100000 digits".to_string()),
+            run("2 3000000000 pow")
+        );
+        assert_eq!(
+            Ok("[1, -1]".to_string()),
+            run("1 3000000000 pow -1 3000000001 pow")
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let run = |src: &str| {
+            let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+            interpreter.run().map_err(|e| e.to_string())?;
+            Ok::<_, String>(super::format_stack(interpreter.get_stack()))
+        };
+
+        assert_eq!(
+            Ok("[true, false, true, true, false]".to_string()),
+            run("2 2 >= 1 2 >= 3 2 >= 2 2 <= 2 1 <=")
+        );
+        assert_eq!(
+            Ok("[true, false, true, true]".to_string()),
+            run("18446744073709551615 18446744073709551614 > \
+                 9007199254740993 9007199254740992.0 == \
+                 9007199254740993 9007199254740992.0 > \
+                 100000000000000000000 -1 >")
+        );
+        assert_eq!(
+            Ok("[true, true, false, true, true, false]".to_string()),
+            run("\"apple\" \"banana\" < \"b\" \"abc\" > false true >= \
+                 { 1 \"a\" } { 1 \"b\" } < { 1 2 } { 1 } > 0 0 / 0 0 / ==")
+        );
+        assert_eq!(
+            Err("`<` can't compare string and number".to_string()),
+            run("\"1\" 2 <")
+        );
+        assert_eq!(
+            Err("`==` can't compare bool and number".to_string()),
+            run("true 1 ==")
+        );
+        assert_eq!(
+            Err("`>` can't compare quotation and quotation".to_string()),
+            run("[ 1 ] [ 1 ] >")
+        );
+    }
+
     #[test]
     fn comments() {
         let src = "# Multiply # fdef mul : number  * fend 4 2 mul # Output: 8 #";
@@ -380,7 +1423,7 @@ mod tests {
         interpreter.run().unwrap();
 
         let mut expected_stack: Stack<StackValue> = Stack::new();
-        expected_stack.push(8.0.into());
+        expected_stack.push(8_u64.into());
 
         assert_eq!(&expected_stack, interpreter.get_stack());
     }
@@ -1,16 +1,103 @@
-use crate::{errors, stack::Stack};
+use crate::{
+    bigint::BigInt, built_in_words, constants, errors, interpreter::StackValue, stack::Stack,
+};
 
 use std::{
+    collections::HashSet,
     iter::Peekable,
     str::{Chars, FromStr},
 };
 
+/// Words that can't be used as the name of a constant or a local variable
+const KEYWORDS: &[&str] = &[
+    "if", "end", "test", "fdef", "fend", "return", "const", "let", "in", "var", "@", "!", "[", "]",
+    "{", "}", "true", "false",
+];
+
+/// Builtin words allowed in constant expressions, they have no side effects
+const CONSTANT_WORDS: &[&str] = &[
+    "drop",
+    "dup",
+    "swap",
+    "+",
+    "-",
+    "*",
+    "/",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "==",
+    "!=",
+    "to_int",
+    "to_uint",
+    "to_float",
+    "to_string",
+    "to_bool",
+    "and",
+    "or",
+    "xor",
+    "not",
+    "shl",
+    "shr",
+    "mod",
+    "divmod",
+    "abs",
+    "neg",
+    "min",
+    "max",
+    "pow",
+    "sqrt",
+];
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     content: Peekable<Chars<'a>>,
     source: &'a str,
     pos: usize,
+    line: usize,
     time: bool,
+    token_start: usize,
+    token_line: usize,
+    error: Option<(errors::LexError, Span)>,
+    /// Every constant defined so far
+    constants: HashSet<String>,
+    /// Every global variable declared so far
+    variables: HashSet<String>,
+    /// Local variables in scope, indexed by their frame slot
+    locals: Vec<String>,
+    /// Blocks waiting for their `end`, innermost last
+    blocks: Vec<Block>,
+    in_function: bool,
+}
+
+/// Block opened by `if`, `test`, `let`, `const`, `[` or `{`
+#[derive(Debug)]
+struct Block {
+    keyword: &'static str,
+    /// Number of local variables it introduced
+    locals: usize,
+    span: Span,
+}
+
+impl Block {
+    /// Word that closes the block
+    fn closer(&self) -> &'static str {
+        match self.keyword {
+            "[" => "]",
+            "{" => "}",
+            _ => "end",
+        }
+    }
+}
+
+/// Location of a token in the source, `start` and `end` are offsets into the
+/// source and `line` is 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
 }
 
 #[derive(Debug)]
@@ -18,6 +105,8 @@ enum Token<'a> {
     Symbol(&'a str),
     NumericLiteral(&'a str),
     StringLiteral(&'a str),
+    Comment(&'a str),
+    DocComment(&'a str),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,6 +115,8 @@ pub enum ArgumentType {
     Bool,
     String,
     Number,
+    List,
+    Quotation,
 }
 
 impl FromStr for ArgumentType {
@@ -37,25 +128,58 @@ impl FromStr for ArgumentType {
             "bool" => Ok(Self::Bool),
             "string" => Ok(Self::String),
             "number" => Ok(Self::Number),
+            "list" => Ok(Self::List),
+            "quotation" => Ok(Self::Quotation),
             _ => Err(()),
         }
     }
 }
 
+impl std::fmt::Display for ArgumentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nothing => write!(f, "!"),
+            Self::Bool => write!(f, "bool"),
+            Self::String => write!(f, "string"),
+            Self::Number => write!(f, "number"),
+            Self::List => write!(f, "list"),
+            Self::Quotation => write!(f, "quotation"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionInfo {
     pub name: String,
-    return_type: ArgumentType,
+    /// Types of the values the function takes, bottom first. Optional in the
+    /// header, so empty also means they were not declared
+    pub inputs: Vec<ArgumentType>,
+    pub return_type: ArgumentType,
     pub pos: usize,
+    /// Number of local variable slots every call needs
+    pub locals: usize,
 }
 
 impl FunctionInfo {
-    fn new(name: String, return_type: ArgumentType) -> Self {
+    fn new(name: String, inputs: Vec<ArgumentType>, return_type: ArgumentType) -> Self {
         FunctionInfo {
             name,
+            inputs,
             return_type,
             pos: 0,
+            locals: 0,
+        }
+    }
+}
+
+/// Formats the header without `fdef`, e.g. `add number number : number`
+impl std::fmt::Display for FunctionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        for input in &self.inputs {
+            write!(f, " {}", input)?;
         }
+        write!(f, " : {}", self.return_type)
     }
 }
 
@@ -64,27 +188,131 @@ pub enum ILToken {
     PushString(String),
     PushUnsignedInteger(u64),
     PushSignedInteger(i64),
+    /// Integer literal that fits neither `u64` nor `i64`
+    PushBigInteger(BigInt),
     PushFloat(f64),
     PushBoolean(u64),
     Symbol(String),
+    /// Call of a function as the last thing it does before returning, reuses
+    /// the frame of the caller instead of growing the return stack
+    TailCall(String),
     If(usize),
+    /// Name of the test and position of its `end`
+    Test(String, usize),
     FuncDef(FunctionInfo),
     FuncEnd,
+    /// Leaves the current function
+    Return,
+    /// `[`, pushes the code up to the matching `]` at this position as a
+    /// quotation instead of running it
+    Quotation(usize),
+    /// `]`, leaves the running quotation
+    QuotationEnd,
+    /// `{`, the values pushed until the matching `}` become a list
+    ListStart,
+    ListEnd,
     End,
-    CommentMarker,
+    Comment(String),
+    /// Documentation of the function defined right after it
+    DocComment(String),
+    /// Definition of a constant and the expression giving its value, its uses
+    /// are replaced by the value. Only kept by `tokenize`
+    Const(String, Vec<ILToken>),
+    /// Names bound to the top values of the stack until the matching `end`,
+    /// and the frame slot of the first one
+    Let(Vec<String>, usize),
+    /// Pushes the local variable in this frame slot
+    Local(usize),
+    /// Declaration of a global variable, and the type of the values it can
+    /// hold if declared
+    Var(String, Option<ArgumentType>),
+    /// `name @`, pushes the value of the variable
+    Fetch(String),
+    /// `name !`, pops a value into the variable
+    Store(String),
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(string: &'a str, time: bool) -> Self {
-        let string = string.trim();
+        let string = string.trim_end();
         Self {
             content: string.chars().peekable(),
             source: string,
             pos: 0,
+            line: 1,
             time,
+            token_start: 0,
+            token_line: 1,
+            error: None,
+            constants: HashSet::new(),
+            variables: HashSet::new(),
+            locals: vec![],
+            blocks: vec![],
+            in_function: false,
         }
     }
 
+    /// Span of the token being lexed
+    fn token_span(&self) -> Span {
+        Span {
+            start: self.token_start,
+            end: self.pos,
+            line: self.token_line,
+        }
+    }
+
+    /// Records the error for the token being lexed, which stops lexing
+    fn fail<T>(&mut self, error: impl Into<errors::LexError>) -> Option<T> {
+        self.error = Some((error.into(), self.token_span()));
+        None
+    }
+
+    fn open_block(&mut self, keyword: &'static str, locals: usize) {
+        let span = self.token_span();
+        self.blocks.push(Block {
+            keyword,
+            locals,
+            span,
+        });
+    }
+
+    /// Fails on the innermost block that is still open when `closer` is
+    /// reached
+    fn check_blocks_closed(&mut self, closer: &str) -> Option<()> {
+        let Some(block) = self.blocks.last() else {
+            return Some(());
+        };
+        let error = errors::LexError::Unbalanced(format!(
+            "`{}` is not closed by `{}` before {}",
+            block.keyword,
+            block.closer(),
+            closer
+        ));
+        self.error = Some((error, block.span));
+        None
+    }
+
+    /// Closes the innermost block, which has to be one closed by `closer`.
+    /// `expected` lists the blocks `closer` can close for the error message
+    fn pop_block(&mut self, closer: &str, expected: &str) -> Option<Block> {
+        match self.blocks.last() {
+            Some(block) if block.closer() == closer => self.blocks.pop(),
+            Some(_) => {
+                self.check_blocks_closed(&format!("`{}`", closer));
+                None
+            }
+            None => self.fail(errors::LexError::Unbalanced(format!(
+                "`{}` without {} to close",
+                closer, expected
+            ))),
+        }
+    }
+
+    /// The error that stopped lexing, if any
+    pub fn error(&self) -> Option<&(errors::LexError, Span)> {
+        self.error.as_ref()
+    }
+
     fn trim_whitespace(&mut self) {
         while self.content.peek().is_some_and(|x| x.is_whitespace()) {
             self.next();
@@ -92,59 +320,147 @@ impl<'a> Lexer<'a> {
     }
 
     fn next(&mut self) -> Option<char> {
-        self.pos += 1;
-        self.content.next()
+        let c = self.content.next()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
     }
 
     fn parse_number(&mut self) -> Result<Token<'a>, errors::NumberParseError> {
         let saved_pos = self.pos;
-        let negative = self.content.peek().is_some_and(|&x| x == '-');
-        let mut points = vec![];
-
-        if negative {
+        while self.content.peek().is_some_and(|x| !x.is_whitespace()) {
             self.next();
         }
 
-        while self.content.peek().is_some_and(|x| !x.is_whitespace()) {
-            if self.next().is_some_and(|x| x == '.') {
-                points.push(self.pos - saved_pos);
-            }
+        let buffer = &self.source[saved_pos..self.pos];
+        Self::validate_number(buffer)?;
+        Ok(Token::NumericLiteral(buffer))
+    }
+
+    /// Base of a number literal given its digits after the sign, and the
+    /// length of its prefix
+    fn number_radix(digits: &str) -> (u32, usize) {
+        match digits.get(..2) {
+            Some("0x" | "0X") => (16, 2),
+            Some("0b" | "0B") => (2, 2),
+            Some("0o" | "0O") => (8, 2),
+            _ => (10, 0),
         }
+    }
 
-        let buffer = &self.source[saved_pos..self.pos];
+    /// Type suffix at the end of a number literal: `i`, `u` or, for decimal
+    /// literals, `f`
+    fn number_suffix(literal: &str, radix: u32) -> Option<char> {
+        literal
+            .chars()
+            .last()
+            .filter(|&c| c == 'i' || c == 'u' || (c == 'f' && radix == 10))
+    }
 
+    /// Checks a literal like `-12`, `1_000`, `0xFF`, `0b1010`, `0o755`, `2.5`,
+    /// `6.02e23` or `5i`, errors point at the offending char
+    fn validate_number(literal: &str) -> Result<(), errors::NumberParseError> {
+        use errors::NumberParseErrorType::*;
+        let error = |error_type, pos| {
+            Err(errors::NumberParseError::new(
+                error_type,
+                literal.to_string(),
+                pos,
+            ))
+        };
+
+        let chars: Vec<char> = literal.chars().collect();
+        let start = (chars[0] == '-') as usize;
         // NOTE: If the only char in number is `-`
-        if negative && buffer.len() == 1 {
-            return Err(errors::NumberParseError::new(
-                errors::NumberParseErrorType::OnlyNegativeSign,
-                buffer.to_string(),
-                0,
-            ));
+        if start == chars.len() {
+            return error(OnlyNegativeSign, 0);
         }
-
-        if points.len() > 1 {
-            return Err(errors::NumberParseError::new(
-                errors::NumberParseErrorType::WrongFloat,
-                buffer.to_string(),
-                points[2],
-            ));
+        let (radix, prefix) = Self::number_radix(&literal[start..]);
+        let digits_start = start + prefix;
+        let suffix = Self::number_suffix(literal, radix);
+        let end = chars.len() - suffix.is_some() as usize;
+        if digits_start == end {
+            return error(MissingDigits, digits_start - 1);
         }
 
-        for i in negative as usize..buffer.len() {
-            let c = buffer.chars().nth(i).unwrap();
-            if c == '.' {
-                continue;
+        let is_digit = |i: usize| chars.get(i).is_some_and(|c| c.is_digit(radix));
+        let mut point = false;
+        let mut exponent = None;
+        for (i, &c) in chars.iter().enumerate().take(end).skip(digits_start) {
+            match c {
+                _ if c.is_digit(radix) => {}
+                '_' if i > digits_start && is_digit(i - 1) && is_digit(i + 1) => {}
+                '_' => return error(MisplacedSeparator, i),
+                '.' if radix == 10 && !point && exponent.is_none() => point = true,
+                '.' if radix == 10 => return error(WrongFloat, i),
+                'e' | 'E' if radix == 10 && exponent.is_none() && i > digits_start => {
+                    exponent = Some(i)
+                }
+                '+' | '-' if exponent.is_some_and(|e| e + 1 == i) => {}
+                _ if radix != 10 && c.is_ascii_alphanumeric() => return error(InvalidDigit, i),
+                _ => return error(NonNumericChar, i),
             }
-            if c.is_alphabetic() || !c.is_ascii_digit() {
-                return Err(errors::NumberParseError::new(
-                    errors::NumberParseErrorType::NonNumericChar,
-                    buffer.to_string(),
-                    i,
-                ));
+        }
+
+        if let Some(exponent) = exponent {
+            let has_digits = chars[exponent + 1..end].iter().any(|c| c.is_ascii_digit());
+            if !has_digits {
+                return error(WrongExponent, exponent);
             }
         }
+        if matches!(suffix, Some('i' | 'u')) && (point || exponent.is_some()) {
+            return error(WrongSuffix, end);
+        }
+        Ok(())
+    }
 
-        Ok(Token::NumericLiteral(buffer))
+    /// Value of a literal checked by `validate_number`
+    fn number_token(&mut self, literal: &str) -> Option<ILToken> {
+        let (negative, unsigned) = match literal.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, literal),
+        };
+        let (radix, prefix) = Self::number_radix(unsigned);
+        let suffix = Self::number_suffix(unsigned, radix);
+        let digits: String = unsigned[prefix..unsigned.len() - suffix.is_some() as usize]
+            .chars()
+            .filter(|&c| c != '_')
+            .collect();
+
+        if suffix == Some('f') || (radix == 10 && digits.contains(['.', 'e', 'E'])) {
+            let sign = if negative { "-" } else { "" };
+            return match format!("{}{}", sign, digits).parse::<f64>() {
+                Ok(num) => Some(ILToken::PushFloat(num)),
+                Err(e) => self.fail(errors::LexError::Literal(format!("{}: {}", e, literal))),
+            };
+        }
+        let Some(int) = BigInt::parse_radix(&digits, radix) else {
+            return self.fail(errors::LexError::Literal(format!(
+                "Invalid integer literal: {}",
+                literal
+            )));
+        };
+        let int = if negative { int.neg() } else { int };
+        let small = int.to_i128();
+        match suffix {
+            Some('i') => match small.and_then(|int| i64::try_from(int).ok()) {
+                Some(int) => Some(ILToken::PushSignedInteger(int)),
+                None => self.fail(errors::LexError::Literal(format!(
+                    "Literal doesn't fit in a signed integer: {}",
+                    literal
+                ))),
+            },
+            Some('u') => match small.and_then(|int| u64::try_from(int).ok()) {
+                Some(int) => Some(ILToken::PushUnsignedInteger(int)),
+                None => self.fail(errors::LexError::Literal(format!(
+                    "Literal doesn't fit in an unsigned integer: {}",
+                    literal
+                ))),
+            },
+            _ => StackValue::from(int).try_into().ok(),
+        }
     }
 
     // TODO: Escaped strings
@@ -161,11 +477,9 @@ impl<'a> Lexer<'a> {
             self.next();
         }
 
+        let buffer = &self.source[saved_pos + 1..self.pos];
         // Skip closing quote
-        self.pos += 1;
-        let buffer = &self.source[saved_pos + 1..self.pos - 1];
-        // NOTE: Can't use self.next() here (things go out of bounds in tests)
-        if self.content.next().is_none() {
+        if self.next().is_none() {
             return Err(errors::UnterminatedStringError);
         }
 
@@ -184,6 +498,38 @@ impl<'a> Lexer<'a> {
         Token::Symbol(buffer)
     }
 
+    /// Comments start and end with a standalone `marker`, `#` for comments
+    /// and `##` for doc comments, everything in between is kept verbatim. An
+    /// unterminated comment runs until the end of the source
+    fn parse_comment(&mut self, marker: &str) -> &'a str {
+        // Skip opening marker
+        for _ in marker.chars() {
+            self.next();
+        }
+        let saved_pos = self.pos;
+
+        while self.pos < self.source.len() {
+            let rest = &self.source[self.pos..];
+            let closes = rest.starts_with(marker)
+                && (self.pos == saved_pos
+                    || self.source[..self.pos].ends_with(|c: char| c.is_whitespace()))
+                && rest[marker.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|x| x.is_whitespace());
+            if closes {
+                let comment = &self.source[saved_pos..self.pos];
+                for _ in marker.chars() {
+                    self.next();
+                }
+                return comment;
+            }
+            self.next();
+        }
+
+        &self.source[saved_pos..]
+    }
+
     fn next_raw(&mut self) -> Option<Token<'a>> {
         self.trim_whitespace();
 
@@ -193,27 +539,30 @@ impl<'a> Lexer<'a> {
         let mut cloned = self.content.clone();
         cloned.next();
         let next_char = cloned.next();
+        if current_char == '#' && next_char.is_none_or(|x| x.is_whitespace()) {
+            return Some(Token::Comment(self.parse_comment("#")));
+        }
+        if current_char == '#'
+            && next_char == Some('#')
+            && cloned.next().is_none_or(|x| x.is_whitespace())
+        {
+            return Some(Token::DocComment(self.parse_comment("##")));
+        }
         if current_char == '"' || current_char == '\'' {
-            match self.parse_string() {
-                Ok(str) => return Some(str),
-                Err(e) => {
-                    eprintln!("{}", e);
-                    return None;
-                }
-            }
+            return match self.parse_string() {
+                Ok(str) => Some(str),
+                Err(e) => self.fail(e),
+            };
         }
         if current_char.is_ascii_digit()
             || (current_char == '-' && next_char.is_some_and(|x| x.is_ascii_digit()))
         {
-            match self.parse_number() {
-                Ok(num) => return Some(num),
-                Err(e) => {
-                    eprintln!("{}", e);
-                    return None;
-                }
-            }
+            return match self.parse_number() {
+                Ok(num) => Some(num),
+                Err(e) => self.fail(e),
+            };
         }
-        return Some(self.parse_symbol());
+        Some(self.parse_symbol())
     }
 
     fn next_processed(&mut self) -> Option<ILToken> {
@@ -221,101 +570,447 @@ impl<'a> Lexer<'a> {
 
         match token {
             Token::StringLiteral(str) => Some(ILToken::PushString(str.to_string())),
-            Token::NumericLiteral(num) => {
-                if num.contains('.') {
-                    match num.parse::<f64>() {
-                        Ok(num) => return Some(ILToken::PushFloat(num)),
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            return None;
-                        }
-                    }
+            Token::Comment(text) => Some(ILToken::Comment(text.trim().to_string())),
+            Token::DocComment(text) => Some(ILToken::DocComment(text.trim().to_string())),
+            Token::NumericLiteral(num) => self.number_token(num),
+            Token::Symbol(name) => match name {
+                "if" => {
+                    self.open_block("if", 0);
+                    Some(ILToken::If(0))
                 }
-                if num.chars().nth(0).is_some_and(|x| x == '-') {
-                    match num.parse::<i64>() {
-                        Ok(num) => return Some(ILToken::PushSignedInteger(num)),
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            return None;
-                        }
-                    }
+                "end" => {
+                    let block = self.pop_block("end", "an `if`, `test`, `let` or `const`")?;
+                    self.locals
+                        .truncate(self.locals.len().saturating_sub(block.locals));
+                    Some(ILToken::End)
                 }
-                match num.parse::<u64>() {
-                    Ok(num) => Some(ILToken::PushUnsignedInteger(num)),
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        None
+                "let" => self.parse_let(),
+                "test" => match self.next_raw() {
+                    Some(Token::StringLiteral(name)) => {
+                        self.open_block("test", 0);
+                        Some(ILToken::Test(name.to_string(), 0))
                     }
-                }
-            }
-            Token::Symbol(name) => match name {
-                "if" => Some(ILToken::If(0)),
-                "end" => Some(ILToken::End),
-                "fdef" => match self.next_raw() {
-                    Some(Token::Symbol(name)) => {
-                        self.next_raw();
-                        match self.next_raw().unwrap() {
-                            Token::Symbol(ret_type) => Some(ILToken::FuncDef(FunctionInfo::new(
-                                name.to_string(),
-                                ArgumentType::from_str(ret_type).unwrap(),
-                            ))),
-                            _ => None,
+                    _ => self.fail(errors::LexError::TestHeader(
+                        "Expected the name of the test as a string after `test`".to_string(),
+                    )),
+                },
+                "fdef" if self.in_function => self.fail(errors::LexError::FunctionHeader(
+                    "`fdef` can't be used inside a function, close it with `fend` first"
+                        .to_string(),
+                )),
+                "fdef" => {
+                    let Some(Token::Symbol(name)) = self.next_raw() else {
+                        return self.fail(errors::LexError::FunctionHeader(
+                            "Expected a function name after `fdef`".to_string(),
+                        ));
+                    };
+                    let mut inputs = vec![];
+                    let mut return_type = None;
+                    while let Some(Token::Symbol(symbol)) = self.next_raw() {
+                        if symbol == ":" {
+                            return_type = match self.next_raw() {
+                                Some(Token::Symbol(ret_type)) => {
+                                    ArgumentType::from_str(ret_type).ok()
+                                }
+                                _ => None,
+                            };
+                            break;
+                        }
+                        match ArgumentType::from_str(symbol) {
+                            Ok(ArgumentType::Nothing) | Err(_) => break,
+                            Ok(input) => inputs.push(input),
                         }
                     }
-                    _ => None,
-                },
-                "fend" => Some(ILToken::FuncEnd),
-                "#" => Some(ILToken::CommentMarker),
+                    self.in_function = true;
+                    match return_type {
+                        Some(return_type) => Some(ILToken::FuncDef(FunctionInfo::new(
+                            name.to_string(),
+                            inputs,
+                            return_type,
+                        ))),
+                        None => self.fail(errors::LexError::FunctionHeader(format!(
+                            "Expected `{} [<type>...] : <!|type>`, where a type is one of bool, string, number, list or quotation",
+                            name
+                        ))),
+                    }
+                }
+                "fend" if !self.in_function => self.fail(errors::LexError::FunctionHeader(
+                    "`fend` without a function to close".to_string(),
+                )),
+                "fend" => {
+                    self.check_blocks_closed("`fend`")?;
+                    self.in_function = false;
+                    self.locals.clear();
+                    Some(ILToken::FuncEnd)
+                }
+                "return" if !self.in_function => self.fail(errors::LexError::Return(
+                    "`return` can only be used inside a function".to_string(),
+                )),
+                // NOTE: A quotation runs in its own frame, `return` would
+                // only leave the quotation and not the function
+                "return" if self.blocks.iter().any(|block| block.keyword == "[") => {
+                    self.fail(errors::LexError::Return(
+                        "`return` can't be used inside a quotation".to_string(),
+                    ))
+                }
+                "return" => Some(ILToken::Return),
+                "[" => {
+                    self.open_block("[", 0);
+                    Some(ILToken::Quotation(0))
+                }
+                "]" => {
+                    self.pop_block("]", "a `[`")?;
+                    Some(ILToken::QuotationEnd)
+                }
+                "{" => {
+                    self.open_block("{", 0);
+                    Some(ILToken::ListStart)
+                }
+                "}" => {
+                    self.pop_block("}", "a `{`")?;
+                    Some(ILToken::ListEnd)
+                }
+                "const" => self.parse_const(),
+                "var" => self.parse_var(),
                 "false" => Some(ILToken::PushBoolean(0)),
                 "true" => Some(ILToken::PushBoolean(1)),
-                _ => Some(ILToken::Symbol(name.to_string())),
+                _ => {
+                    if let Some(slot) = self.locals.iter().rposition(|local| local == name) {
+                        return Some(ILToken::Local(slot));
+                    }
+                    if !self.variables.contains(name) {
+                        return Some(ILToken::Symbol(name.to_string()));
+                    }
+                    match self.next_raw() {
+                        Some(Token::Symbol("@")) => Some(ILToken::Fetch(name.to_string())),
+                        Some(Token::Symbol("!")) => Some(ILToken::Store(name.to_string())),
+                        _ => self.fail(errors::LexError::Variable(format!(
+                            "Expected `@` or `!` after the variable `{}`",
+                            name
+                        ))),
+                    }
+                }
             },
         }
     }
 
+    /// Parses `const NAME <expression> end` after the `const` keyword. The
+    /// expression is evaluated by `constants::fold` once the whole source is
+    /// tokenized
+    fn parse_const(&mut self) -> Option<ILToken> {
+        let name = match self.next_raw() {
+            Some(Token::Symbol(name)) if !KEYWORDS.contains(&name) => name,
+            _ => {
+                return self.fail(errors::LexError::Constant(
+                    "Expected a constant name after `const`".to_string(),
+                ))
+            }
+        };
+        if self.constants.contains(name) || self.variables.contains(name) {
+            return self.fail(errors::LexError::Constant(format!(
+                "`{}` is already defined",
+                name
+            )));
+        }
+        if built_in_words::find_builtin(name).is_some() {
+            return self.fail(errors::LexError::Constant(format!(
+                "`{}` is a builtin word",
+                name
+            )));
+        }
+
+        // NOTE: Popped by the constant's `end`
+        self.open_block("const", 0);
+        let mut body = vec![];
+        loop {
+            self.trim_whitespace();
+            let start = self.pos;
+            match self.next_processed() {
+                Some(ILToken::End) => break,
+                Some(ILToken::Comment(_) | ILToken::DocComment(_)) => {}
+                Some(
+                    token @ (ILToken::PushString(_)
+                    | ILToken::PushUnsignedInteger(_)
+                    | ILToken::PushSignedInteger(_)
+                    | ILToken::PushBigInteger(_)
+                    | ILToken::PushFloat(_)
+                    | ILToken::PushBoolean(_)),
+                ) => body.push(token),
+                Some(ILToken::Symbol(word))
+                    if CONSTANT_WORDS.contains(&word.as_str())
+                        || self.constants.contains(&word) =>
+                {
+                    body.push(ILToken::Symbol(word))
+                }
+                Some(_) => {
+                    return self.fail(errors::LexError::Constant(format!(
+                        "`{}` can't be used in the value of the constant `{}`",
+                        &self.source[start..self.pos],
+                        name
+                    )))
+                }
+                None if self.error.is_some() => return None,
+                None => {
+                    return self.fail(errors::LexError::Constant(format!(
+                        "Expected `end` after the value of the constant `{}`",
+                        name
+                    )))
+                }
+            }
+        }
+
+        self.constants.insert(name.to_string());
+        Some(ILToken::Const(name.to_string(), body))
+    }
+
+    /// Parses `var NAME [type]` after the `var` keyword
+    fn parse_var(&mut self) -> Option<ILToken> {
+        if self.in_function {
+            return self.fail(errors::LexError::Variable(
+                "Variables can't be declared inside a function".to_string(),
+            ));
+        }
+        let name = match self.next_raw() {
+            Some(Token::Symbol(name)) if !KEYWORDS.contains(&name) => name,
+            _ => {
+                return self.fail(errors::LexError::Variable(
+                    "Expected a variable name after `var`".to_string(),
+                ))
+            }
+        };
+        if self.constants.contains(name) || self.variables.contains(name) {
+            return self.fail(errors::LexError::Variable(format!(
+                "`{}` is already defined",
+                name
+            )));
+        }
+        if built_in_words::find_builtin(name).is_some() {
+            return self.fail(errors::LexError::Variable(format!(
+                "`{}` is a builtin word",
+                name
+            )));
+        }
+
+        // NOTE: The type is optional, put back whatever follows otherwise
+        let saved = (self.content.clone(), self.pos, self.line);
+        let declared = match self.next_raw() {
+            Some(Token::Symbol(symbol)) => ArgumentType::from_str(symbol)
+                .ok()
+                .filter(|t| *t != ArgumentType::Nothing),
+            _ => None,
+        };
+        if declared.is_none() {
+            (self.content, self.pos, self.line) = saved;
+            self.error = None;
+        }
+
+        self.variables.insert(name.to_string());
+        Some(ILToken::Var(name.to_string(), declared))
+    }
+
+    /// Parses `let NAME... in` after the `let` keyword, the names stay in
+    /// scope until the matching `end`
+    fn parse_let(&mut self) -> Option<ILToken> {
+        if !self.in_function {
+            return self.fail(errors::LexError::Let(
+                "`let` can only be used inside a function".to_string(),
+            ));
+        }
+
+        let mut names: Vec<String> = vec![];
+        loop {
+            match self.next_raw() {
+                Some(Token::Symbol("in")) if !names.is_empty() => break,
+                Some(Token::Symbol(name)) if !KEYWORDS.contains(&name) => {
+                    if names.iter().any(|n| n == name) {
+                        return self.fail(errors::LexError::Let(format!(
+                            "`{}` is bound twice by the same `let`",
+                            name
+                        )));
+                    }
+                    names.push(name.to_string());
+                }
+                _ => {
+                    return self.fail(errors::LexError::Let(
+                        "Expected `let <name>... in`".to_string(),
+                    ))
+                }
+            }
+        }
+
+        let first = self.locals.len();
+        self.open_block("let", names.len());
+        self.locals.extend(names.iter().cloned());
+        Some(ILToken::Let(names, first))
+    }
+
+    fn next_spanned(&mut self) -> Option<(ILToken, Span)> {
+        self.trim_whitespace();
+        let start = self.pos;
+        let line = self.line;
+        self.token_start = start;
+        self.token_line = line;
+        let token = self.next_processed()?;
+
+        Some((
+            token,
+            Span {
+                start,
+                end: self.pos,
+                line,
+            },
+        ))
+    }
+
+    /// Fills in the position of the `end` of every block and the number of
+    /// local variable slots of every function
     fn cross_reference_blocks(program: Vec<ILToken>) -> Vec<ILToken> {
         let mut result = program.clone();
         let mut stack: Stack<usize> = Stack::new();
+        let mut quotations: Stack<usize> = Stack::new();
+        let mut function = None;
         for (i, token) in program.iter().enumerate() {
             match &token {
                 ILToken::End => {
-                    let if_ip = stack.pop().unwrap();
-                    result[if_ip] = ILToken::If(i);
+                    // NOTE: The lexer reports an `end` that closes nothing
+                    if let Some(start) = stack.pop() {
+                        Self::close_block(&mut result, start, i);
+                    }
                 }
-                ILToken::If(_) => {
+                ILToken::If(_) | ILToken::Test(..) => {
                     stack.push(i);
                 }
+                ILToken::Let(names, first) => {
+                    stack.push(i);
+                    if let Some(ILToken::FuncDef(info)) = function.map(|def| &mut result[def]) {
+                        info.locals = info.locals.max(first + names.len());
+                    }
+                }
+                ILToken::Quotation(_) => quotations.push(i),
+                ILToken::QuotationEnd => {
+                    if let Some(start) = quotations.pop() {
+                        Self::close_block(&mut result, start, i);
+                    }
+                }
+                ILToken::FuncDef(_) => function = Some(i),
+                ILToken::FuncEnd => function = None,
 
                 _ => {}
             }
         }
+        // NOTE: Blocks the lexer reported as never closed end with the program
+        while let Some(start) = stack.pop().or_else(|| quotations.pop()) {
+            Self::close_block(&mut result, start, program.len());
+        }
 
+        Self::mark_tail_calls(&mut result);
         result
     }
 
-    pub fn parse(mut self) -> Vec<ILToken> {
+    /// Points the block starting at `start` to its `end` at `end`
+    fn close_block(program: &mut [ILToken], start: usize, end: usize) {
+        match &mut program[start] {
+            ILToken::Test(_, position) | ILToken::If(position) | ILToken::Quotation(position) => {
+                *position = end
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces calls of functions followed by the end of the function (or
+    /// `return`) with tail calls. Only `end`s and comments may come between,
+    /// calls inside quotations return to the quotation so they are left alone
+    fn mark_tail_calls(program: &mut [ILToken]) {
+        let functions: HashSet<String> = program
+            .iter()
+            .filter_map(|token| match token {
+                ILToken::FuncDef(info) if built_in_words::find_builtin(&info.name).is_none() => {
+                    Some(info.name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut quotations = 0;
+        let mut in_function = false;
+        for i in 0..program.len() {
+            match &program[i] {
+                ILToken::FuncDef(_) => in_function = true,
+                ILToken::FuncEnd => in_function = false,
+                ILToken::Quotation(_) => quotations += 1,
+                ILToken::QuotationEnd => quotations -= 1,
+                ILToken::Symbol(name) if in_function && quotations == 0 => {
+                    let next = program[i + 1..].iter().find(|token| {
+                        !matches!(
+                            token,
+                            ILToken::End | ILToken::Comment(_) | ILToken::DocComment(_)
+                        )
+                    });
+                    if functions.contains(name)
+                        && matches!(next, Some(ILToken::FuncEnd | ILToken::Return))
+                    {
+                        program[i] = ILToken::TailCall(name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn parse(&mut self) -> Vec<ILToken> {
+        self.parse_with_spans().0
+    }
+
+    /// Tokenizes the source and folds constants without cross referencing
+    /// blocks. Stops at the first error, which is then available through
+    /// `error`
+    pub fn tokenize(&mut self) -> (Vec<ILToken>, Vec<Span>) {
         let mut program = vec![];
-        if self.time {
-            let mut now = std::time::Instant::now();
+        let mut spans = vec![];
 
-            while let Some(token) = self.next_processed() {
-                program.push(token);
+        while let Some((token, span)) = self.next_spanned() {
+            program.push(token);
+            spans.push(span);
+        }
+        if self.error.is_none() {
+            self.check_blocks_closed("the end of the file");
+        }
+        if self.error.is_none() {
+            if let Err((e, position)) = constants::fold(&mut program) {
+                self.error = Some((e, spans[position]));
+                program.truncate(position);
+                spans.truncate(position);
             }
+        }
 
-            let mut elapsed = now.elapsed();
-            eprintln!("Parsing program took: {:?}", elapsed);
+        (program, spans)
+    }
 
-            now = std::time::Instant::now();
-            let tokens = Self::cross_reference_blocks(program);
-            elapsed = now.elapsed();
-            eprintln!("Cross referencing blocks took: {:?}", elapsed);
-            return tokens;
+    /// Same as `parse`, but also returns the span of every token
+    pub fn parse_with_spans(&mut self) -> (Vec<ILToken>, Vec<Span>) {
+        let now = std::time::Instant::now();
+        let (program, spans) = self.tokenize();
+        if let Some((e, _)) = &self.error {
+            eprintln!("{}", e);
         }
+        // NOTE: Constants are already inlined, their definitions do nothing
+        let (program, spans): (Vec<ILToken>, Vec<Span>) = program
+            .into_iter()
+            .zip(spans)
+            .filter(|(token, _)| !matches!(token, ILToken::Const(..)))
+            .unzip();
 
-        while let Some(token) = self.next_processed() {
-            program.push(token);
+        if self.time {
+            eprintln!("Parsing program took: {:?}", now.elapsed());
         }
-        Self::cross_reference_blocks(program)
+
+        let now = std::time::Instant::now();
+        let tokens = Self::cross_reference_blocks(program);
+        if self.time {
+            eprintln!("Cross referencing blocks took: {:?}", now.elapsed());
+        }
+
+        (tokens, spans)
     }
 }
 
@@ -325,7 +1020,7 @@ mod tests {
 
     #[test]
     fn empty_program() {
-        let lexer = Lexer::new("", false);
+        let mut lexer = Lexer::new("", false);
         let empty: Vec<ILToken> = vec![];
 
         assert_eq!(empty, lexer.parse());
@@ -333,7 +1028,7 @@ mod tests {
 
     #[test]
     fn parse_u64() {
-        let lexer = Lexer::new("123", false);
+        let mut lexer = Lexer::new("123", false);
         let program = vec![ILToken::PushUnsignedInteger(123)];
 
         assert_eq!(program, lexer.parse());
@@ -341,7 +1036,7 @@ mod tests {
 
     #[test]
     fn parse_i64() {
-        let lexer = Lexer::new("-123", false);
+        let mut lexer = Lexer::new("-123", false);
         let program = vec![ILToken::PushSignedInteger(-123)];
 
         assert_eq!(program, lexer.parse());
@@ -349,7 +1044,7 @@ mod tests {
 
     #[test]
     fn parse_f64() {
-        let lexer = Lexer::new("-420.69", false);
+        let mut lexer = Lexer::new("-420.69", false);
         let program = vec![ILToken::PushFloat(-420.69)];
 
         assert_eq!(program, lexer.parse());
@@ -357,7 +1052,7 @@ mod tests {
 
     #[test]
     fn parse_string() {
-        let lexer = Lexer::new("\"Lotus\"", false);
+        let mut lexer = Lexer::new("\"Lotus\"", false);
         let program = vec![ILToken::PushString("Lotus".to_string())];
 
         assert_eq!(program, lexer.parse());
@@ -365,23 +1060,443 @@ mod tests {
 
     #[test]
     fn parse_symbol() {
-        let lexer = Lexer::new("Lotus", false);
+        let mut lexer = Lexer::new("Lotus", false);
         let program = vec![ILToken::Symbol("Lotus".to_string())];
 
         assert_eq!(program, lexer.parse());
     }
 
+    #[test]
+    fn doc_comments() {
+        let mut lexer = Lexer::new("## Adds ## # ## # fdef add number number : number", false);
+        let program = vec![
+            ILToken::DocComment("Adds".to_string()),
+            ILToken::Comment("##".to_string()),
+            ILToken::FuncDef(FunctionInfo::new(
+                "add".to_string(),
+                vec![ArgumentType::Number, ArgumentType::Number],
+                ArgumentType::Number,
+            )),
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn parse_test_block() {
+        let mut lexer = Lexer::new("test \"adds\" 1 1 + 2 assert_eq end", false);
+        let program = vec![
+            ILToken::Test("adds".to_string(), 6),
+            ILToken::PushUnsignedInteger(1),
+            ILToken::PushUnsignedInteger(1),
+            ILToken::Symbol("+".to_string()),
+            ILToken::PushUnsignedInteger(2),
+            ILToken::Symbol("assert_eq".to_string()),
+            ILToken::End,
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn constants() {
+        let src = "const KIB 1024 end const SIZE 4 KIB * end const NAME \"x\" end SIZE NAME";
+        let program = vec![
+            ILToken::PushUnsignedInteger(4096),
+            ILToken::PushString("x".to_string()),
+        ];
+
+        assert_eq!(program, Lexer::new(src, false).parse());
+    }
+
+    #[test]
+    fn constant_errors() {
+        let error = |src| {
+            let mut lexer = Lexer::new(src, false);
+            lexer.tokenize();
+            lexer.error().unwrap().0.summary()
+        };
+
+        assert_eq!(
+            "`print` can't be used in the value of the constant `A`",
+            error("const A 1 print end")
+        );
+        assert_eq!(
+            "Could not evaluate the constant `A`: Empty stack during execution",
+            error("const A + end")
+        );
+        assert_eq!(
+            "The value of the constant `A` leaves 2 values instead of one",
+            error("const A 1 2 end")
+        );
+        assert_eq!(
+            "`A` is already defined",
+            error("const A 1 end const A 2 end")
+        );
+        assert_eq!(
+            "Expected `end` after the value of the constant `A`",
+            error("const A 1")
+        );
+        assert_eq!("`print` is a builtin word", error("const print 1 end"));
+        assert_eq!("`dup` is a builtin word", error("var dup"));
+    }
+
+    #[test]
+    fn local_variables() {
+        let src = "fdef f : number let a b in a let c in c b end let d in d end end fend";
+        let (tokens, _) = Lexer::new(src, false).parse_with_spans();
+
+        let ILToken::FuncDef(info) = &tokens[0] else {
+            panic!("Expected a function definition");
+        };
+        assert_eq!(3, info.locals);
+        assert_eq!(
+            vec![
+                ILToken::Let(vec!["a".to_string(), "b".to_string()], 0),
+                ILToken::Local(0),
+                ILToken::Let(vec!["c".to_string()], 2),
+                ILToken::Local(2),
+                ILToken::Local(1),
+                ILToken::End,
+                ILToken::Let(vec!["d".to_string()], 2),
+                ILToken::Local(2),
+                ILToken::End,
+                ILToken::End,
+                ILToken::FuncEnd,
+            ],
+            tokens[1..]
+        );
+    }
+
+    #[test]
+    fn let_errors() {
+        let error = |src| {
+            let mut lexer = Lexer::new(src, false);
+            lexer.tokenize();
+            lexer.error().unwrap().0.summary()
+        };
+
+        assert_eq!(
+            "`let` can only be used inside a function",
+            error("1 let a in a end")
+        );
+        assert_eq!(
+            "`a` is bound twice by the same `let`",
+            error("fdef f : ! let a a in end fend")
+        );
+        assert_eq!(
+            "Expected `let <name>... in`",
+            error("fdef f : ! let in end fend")
+        );
+    }
+
+    #[test]
+    fn nested_functions() {
+        let error = |src| {
+            let mut lexer = Lexer::new(src, false);
+            lexer.tokenize();
+            lexer.error().unwrap().0.summary()
+        };
+
+        assert_eq!(
+            "`fdef` can't be used inside a function, close it with `fend` first",
+            error("fdef f : ! fdef g : ! fend fend")
+        );
+        assert_eq!("`fend` without a function to close", error("fend"));
+        assert_eq!(
+            "`fend` without a function to close",
+            error("fdef f : ! fend fend")
+        );
+    }
+
+    #[test]
+    fn unbalanced_blocks() {
+        let error = |src| {
+            let mut lexer = Lexer::new(src, false);
+            lexer.tokenize();
+            lexer.error().map(|(e, span)| (e.summary(), span.line))
+        };
+
+        assert_eq!(
+            Some((
+                "`end` without an `if`, `test`, `let` or `const` to close".to_string(),
+                2
+            )),
+            error("1 if 2 end\nend")
+        );
+        assert_eq!(
+            Some(("`let` is not closed by `end` before `fend`".to_string(), 2)),
+            error("fdef f number : number\nlet a in a\nfend")
+        );
+        assert_eq!(
+            Some((
+                "`if` is not closed by `end` before the end of the file".to_string(),
+                2
+            )),
+            error("test \"t\" true if 1 end\n1 if")
+        );
+        assert_eq!(None, error("fdef f : ! let a in a if end end fend"));
+        assert_eq!(
+            Some(("`]` without a `[` to close".to_string(), 1)),
+            error("1 ]")
+        );
+        assert_eq!(
+            Some(("`}` without a `{` to close".to_string(), 1)),
+            error("{ 1 } }")
+        );
+        assert_eq!(
+            Some((
+                "`[` is not closed by `]` before the end of the file".to_string(),
+                1
+            )),
+            error("[ 1 [ 2 ]")
+        );
+        assert_eq!(
+            Some(("`{` is not closed by `}` before `end`".to_string(), 1)),
+            error("true if { 1 end }")
+        );
+        assert_eq!(
+            Some(("`if` is not closed by `end` before `]`".to_string(), 1)),
+            error("[ 1 if ] end")
+        );
+    }
+
+    #[test]
+    fn return_errors() {
+        let error = |src| {
+            let mut lexer = Lexer::new(src, false);
+            lexer.tokenize();
+            lexer.error().map(|(e, _)| e.summary())
+        };
+
+        assert_eq!(
+            Some("`return` can only be used inside a function".to_string()),
+            error("1 return")
+        );
+        assert_eq!(
+            Some("`return` can't be used inside a quotation".to_string()),
+            error("fdef f : number [ 1 return ] call 2 fend")
+        );
+        assert_eq!(None, error("fdef f : number [ 1 ] call return fend"));
+    }
+
+    #[test]
+    fn variables() {
+        let mut lexer = Lexer::new("var a var b number a @ 1 b !\nvar c \"c\"", false);
+        let program = vec![
+            ILToken::Var("a".to_string(), None),
+            ILToken::Var("b".to_string(), Some(ArgumentType::Number)),
+            ILToken::Fetch("a".to_string()),
+            ILToken::PushUnsignedInteger(1),
+            ILToken::Store("b".to_string()),
+            ILToken::Var("c".to_string(), None),
+            ILToken::PushString("c".to_string()),
+        ];
+
+        assert_eq!(program, lexer.parse());
+
+        let mut lexer = Lexer::new("var a a print", false);
+        lexer.tokenize();
+        assert_eq!(
+            "Expected `@` or `!` after the variable `a`",
+            lexer.error().unwrap().0.summary()
+        );
+    }
+
+    #[test]
+    fn quotations_and_lists() {
+        let mut lexer = Lexer::new("[ [ 1 ] call ] { 1 2 }", false);
+        let program = vec![
+            ILToken::Quotation(5),
+            ILToken::Quotation(3),
+            ILToken::PushUnsignedInteger(1),
+            ILToken::QuotationEnd,
+            ILToken::Symbol("call".to_string()),
+            ILToken::QuotationEnd,
+            ILToken::ListStart,
+            ILToken::PushUnsignedInteger(1),
+            ILToken::PushUnsignedInteger(2),
+            ILToken::ListEnd,
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn tail_calls() {
+        let program = Lexer::new(
+            "fdef f : ! 1 if f end # loop # fend \
+             fdef g : ! f 1 [ f ] call print f return fend",
+            false,
+        )
+        .parse();
+        let calls: Vec<&ILToken> = program
+            .iter()
+            .filter(|token| matches!(token, ILToken::Symbol(_) | ILToken::TailCall(_)))
+            .collect();
+
+        assert_eq!(
+            vec![
+                &ILToken::TailCall("f".to_string()),
+                &ILToken::Symbol("f".to_string()),
+                &ILToken::Symbol("f".to_string()),
+                &ILToken::Symbol("call".to_string()),
+                &ILToken::Symbol("print".to_string()),
+                &ILToken::TailCall("f".to_string()),
+            ],
+            calls
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let mut lexer = Lexer::new("\n1 \"a\"\n  fdef f : number fend", false);
+        let (_, spans) = lexer.parse_with_spans();
+
+        assert_eq!(
+            vec![
+                Span {
+                    start: 1,
+                    end: 2,
+                    line: 2
+                },
+                Span {
+                    start: 3,
+                    end: 6,
+                    line: 2
+                },
+                Span {
+                    start: 9,
+                    end: 24,
+                    line: 3
+                },
+                Span {
+                    start: 25,
+                    end: 29,
+                    line: 3
+                },
+            ],
+            spans
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let mut lexer = Lexer::new("# A # 1 #2 # multi\n line # #", false);
+        let program = vec![
+            ILToken::Comment("A".to_string()),
+            ILToken::PushUnsignedInteger(1),
+            ILToken::Symbol("#2".to_string()),
+            ILToken::Comment("multi\n line".to_string()),
+            ILToken::Comment("".to_string()),
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn non_ascii_spans() {
+        let src = "\"héllo\" wörld";
+        let (tokens, spans) = Lexer::new(src, false).parse_with_spans();
+
+        assert_eq!(ILToken::PushString("héllo".to_string()), tokens[0]);
+        assert_eq!("wörld", &src[spans[1].start..spans[1].end]);
+    }
+
     #[test]
     fn invalid_number() {
-        let lexer = Lexer::new("6942O", false); // Look at it closely
+        let mut lexer = Lexer::new("6942O", false); // Look at it closely
         let program: Vec<ILToken> = vec![];
 
         assert_eq!(program, lexer.parse());
     }
 
+    #[test]
+    fn big_integer_literals() {
+        let mut lexer = Lexer::new("18446744073709551616 -9223372036854775809", false);
+        let program = vec![
+            ILToken::PushBigInteger(BigInt::parse("18446744073709551616").unwrap()),
+            ILToken::PushBigInteger(BigInt::parse("-9223372036854775809").unwrap()),
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn number_bases_and_exponents() {
+        let mut lexer = Lexer::new(
+            "0xFF -0b1010 0o755 1_000_000 6.02e23 1E-3 -2.5e+2 0xffff_ffff_ffff_ffff_f",
+            false,
+        );
+        let program = vec![
+            ILToken::PushUnsignedInteger(255),
+            ILToken::PushSignedInteger(-10),
+            ILToken::PushUnsignedInteger(493),
+            ILToken::PushUnsignedInteger(1_000_000),
+            ILToken::PushFloat(6.02e23),
+            ILToken::PushFloat(1e-3),
+            ILToken::PushFloat(-250.0),
+            ILToken::PushBigInteger(BigInt::parse("295147905179352825855").unwrap()),
+        ];
+
+        assert_eq!(program, lexer.parse());
+    }
+
+    #[test]
+    fn number_suffixes() {
+        let mut lexer = Lexer::new("5i 5u 5f -3i 0xFFu 1e3f", false);
+        let program = vec![
+            ILToken::PushSignedInteger(5),
+            ILToken::PushUnsignedInteger(5),
+            ILToken::PushFloat(5.0),
+            ILToken::PushSignedInteger(-3),
+            ILToken::PushUnsignedInteger(255),
+            ILToken::PushFloat(1000.0),
+        ];
+
+        assert_eq!(program, lexer.parse());
+        // NOTE: Out of range literals stop the lexer
+        assert_eq!(Vec::<ILToken>::new(), Lexer::new("-5u", false).parse());
+        assert_eq!(
+            Vec::<ILToken>::new(),
+            Lexer::new("9223372036854775808i", false).parse()
+        );
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        use errors::NumberParseErrorType::*;
+        let cases = [
+            ("1.2.3", WrongFloat, 3),
+            ("12a", NonNumericChar, 2),
+            ("0b102", InvalidDigit, 4),
+            ("0xFG", InvalidDigit, 3),
+            ("1__000", MisplacedSeparator, 1),
+            ("1000_", MisplacedSeparator, 4),
+            ("0x_1", MisplacedSeparator, 2),
+            ("1e", WrongExponent, 1),
+            ("2.5e+", WrongExponent, 3),
+            ("-0x", MissingDigits, 2),
+            ("0xu", MissingDigits, 1),
+            ("2.5i", WrongSuffix, 3),
+            ("5iu", NonNumericChar, 1),
+        ];
+
+        for (literal, error_type, pos) in cases {
+            let error = Lexer::validate_number(literal).unwrap_err();
+            assert_eq!(
+                std::mem::discriminant(&error_type),
+                std::mem::discriminant(&error.error_type),
+                "{}",
+                literal
+            );
+            assert_eq!(pos, error.pos, "{}", literal);
+        }
+    }
+
     #[test]
     fn unterminated_string() {
-        let lexer = Lexer::new("\"Lotus", false);
+        let mut lexer = Lexer::new("\"Lotus", false);
         let program: Vec<ILToken> = vec![];
 
         assert_eq!(program, lexer.parse());
@@ -0,0 +1,295 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    built_in_words, checker,
+    diagnostics::{Diagnostic, Severity},
+    lexer::{ILToken, Lexer, Span},
+};
+
+// NOTE: Codes are part of the interface, never reuse or renumber them
+pub const UNUSED_FUNCTION: &str = "L001";
+pub const SHADOWED_BUILTIN: &str = "L002";
+pub const UNREACHABLE_CODE: &str = "L003";
+pub const REDUNDANT_STACK_OPS: &str = "L004";
+pub const CONSTANT_CONDITION: &str = "L005";
+pub const RETURN_TYPE_MISMATCH: &str = "L006";
+pub const UNDECLARED_INPUTS: &str = "L007";
+pub const UNBALANCED_IF: &str = "L008";
+
+/// Comment that suppresses the listed codes, e.g. `# lint-allow L001 L004 #`.
+/// Applies to the line after it when on its own line, otherwise to the
+/// line it ends
+const ALLOW: &str = "lint-allow";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reachability {
+    Reachable,
+    /// After `exit` or `return`, nothing reported yet
+    Exited(&'static str),
+    Reported,
+}
+
+fn unused_and_shadowed(tokens: &[ILToken], spans: &[Span], warnings: &mut Vec<Diagnostic>) {
+    let called: HashSet<&str> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            ILToken::Symbol(name) | ILToken::TailCall(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    for (token, span) in tokens.iter().zip(spans) {
+        let ILToken::FuncDef(info) = token else {
+            continue;
+        };
+        if built_in_words::find_builtin(&info.name).is_some() {
+            warnings.push(
+                Diagnostic::warning(
+                    *span,
+                    format!(
+                        "`{}` shadows a builtin word, calls to it run the builtin",
+                        info.name
+                    ),
+                )
+                .with_code(SHADOWED_BUILTIN),
+            );
+        } else if !called.contains(info.name.as_str()) {
+            warnings.push(
+                Diagnostic::warning(*span, format!("`{}` is never called", info.name))
+                    .with_code(UNUSED_FUNCTION),
+            );
+        }
+    }
+}
+
+fn unreachable(tokens: &[ILToken], spans: &[Span], warnings: &mut Vec<Diagnostic>) {
+    let mut blocks = vec![Reachability::Reachable];
+
+    for (token, span) in tokens.iter().zip(spans) {
+        match token {
+            ILToken::Comment(_) | ILToken::DocComment(_) => continue,
+            ILToken::End | ILToken::FuncEnd | ILToken::QuotationEnd => {
+                if blocks.len() > 1 {
+                    blocks.pop();
+                }
+                continue;
+            }
+            // NOTE: Definitions and tests don't run where they are written
+            ILToken::FuncDef(_) | ILToken::Test(..) => {
+                blocks.push(Reachability::Reachable);
+                continue;
+            }
+            _ => {}
+        }
+
+        let block = blocks.last_mut().unwrap();
+        if let Reachability::Exited(word) = *block {
+            warnings.push(
+                Diagnostic::warning(*span, format!("Unreachable code after `{}`", word))
+                    .with_code(UNREACHABLE_CODE),
+            );
+            *block = Reachability::Reported;
+        }
+        match token {
+            ILToken::Symbol(name) if name == "exit" && *block == Reachability::Reachable => {
+                *block = Reachability::Exited("exit");
+            }
+            ILToken::Return if *block == Reachability::Reachable => {
+                *block = Reachability::Exited("return");
+            }
+            // NOTE: An `exit` in a quotation only stops the code after it in
+            // the quotation, the quotation may never be called
+            ILToken::If(_) | ILToken::Quotation(_) => blocks.push(Reachability::Reachable),
+            ILToken::Let(..) => {
+                let current = *block;
+                blocks.push(current);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn sequences(tokens: &[ILToken], spans: &[Span], warnings: &mut Vec<Diagnostic>) {
+    let code: Vec<(&ILToken, &Span)> = tokens
+        .iter()
+        .zip(spans)
+        .filter(|(token, _)| !matches!(token, ILToken::Comment(_) | ILToken::DocComment(_)))
+        .collect();
+
+    for pair in code.windows(2) {
+        let [(first, first_span), (second, second_span)] = pair else {
+            continue;
+        };
+        let span = Span {
+            start: first_span.start,
+            end: second_span.end,
+            line: first_span.line,
+        };
+        match (first, second) {
+            (ILToken::Symbol(a), ILToken::Symbol(b))
+                if (a == "dup" && b == "drop") || (a == "swap" && b == "swap") =>
+            {
+                warnings.push(
+                    Diagnostic::warning(span, format!("`{} {}` has no effect", a, b))
+                        .with_code(REDUNDANT_STACK_OPS),
+                );
+            }
+            (ILToken::PushBoolean(value), ILToken::If(_)) => {
+                let message = if *value != 0 {
+                    "`if` condition is always true"
+                } else {
+                    "`if` condition is always false, the block never runs"
+                };
+                warnings.push(
+                    Diagnostic::warning(span, message.to_string()).with_code(CONSTANT_CONDITION),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Codes suppressed on every line by `lint-allow` comments
+fn suppressions<'t>(
+    source: &str,
+    tokens: &'t [ILToken],
+    spans: &[Span],
+) -> HashMap<usize, Vec<&'t str>> {
+    let mut allowed: HashMap<usize, Vec<&str>> = HashMap::new();
+    let mut previous_line = 0;
+    for (token, span) in tokens.iter().zip(spans) {
+        let own_line = span.line > previous_line;
+        let last = span.line + source[span.start..span.end].matches('\n').count();
+        previous_line = last;
+
+        let ILToken::Comment(text) = token else {
+            continue;
+        };
+        let Some(codes) = text.strip_prefix(ALLOW) else {
+            continue;
+        };
+        let line = if own_line { last + 1 } else { last };
+        allowed
+            .entry(line)
+            .or_default()
+            .extend(codes.split_whitespace());
+    }
+    allowed
+}
+
+/// Runs the stack checker and the lint rules over `source`. Warnings whose
+/// code is listed in a `lint-allow` comment are left out
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let mut lexer = Lexer::new(source, false);
+    let (tokens, spans) = lexer.tokenize();
+    if let Some((e, span)) = lexer.error() {
+        return vec![Diagnostic::error(*span, e.summary())];
+    }
+
+    let mut diagnostics = checker::check(&tokens, &spans).diagnostics;
+    unused_and_shadowed(&tokens, &spans, &mut diagnostics);
+    unreachable(&tokens, &spans, &mut diagnostics);
+    sequences(&tokens, &spans, &mut diagnostics);
+
+    let allowed = suppressions(source, &tokens, &spans);
+    diagnostics.retain(|diagnostic| {
+        let suppressed = diagnostic.severity == Severity::Warning
+            && diagnostic.code.is_some_and(|code| {
+                allowed
+                    .get(&diagnostic.span.line)
+                    .is_some_and(|codes| codes.contains(&code))
+            });
+        !suppressed
+    });
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(source: &str) -> Vec<(usize, &'static str)> {
+        lint(source)
+            .into_iter()
+            .map(|d| (d.span.line, d.code.unwrap_or("error")))
+            .collect()
+    }
+
+    #[test]
+    fn clean_program() {
+        let src = std::fs::read_to_string("main.iod").unwrap();
+
+        assert_eq!(Vec::<(usize, &str)>::new(), codes(&src));
+    }
+
+    #[test]
+    fn functions() {
+        let src = "\
+fdef unused : ! fend
+fdef dup : ! fend
+fdef used : number 1 fend
+used print";
+
+        assert_eq!(
+            vec![(1, UNUSED_FUNCTION), (2, SHADOWED_BUILTIN)],
+            codes(src)
+        );
+    }
+
+    #[test]
+    fn unreachable_code() {
+        let src = "\
+fdef stop : ! 0 exit \"never\" print fend
+fdef early : ! return 1 print fend early
+true if 1 exit end
+\"reachable\" print
+[ 0 exit ] call [ 1 exit 2 ] call
+stop 0 exit
+2 print 3 print";
+
+        assert_eq!(
+            vec![
+                (1, UNREACHABLE_CODE),
+                (2, UNREACHABLE_CODE),
+                (3, CONSTANT_CONDITION),
+                (5, UNREACHABLE_CODE),
+                (7, UNREACHABLE_CODE)
+            ],
+            codes(src)
+        );
+    }
+
+    #[test]
+    fn redundant_sequences() {
+        assert_eq!(
+            vec![(1, REDUNDANT_STACK_OPS), (2, REDUNDANT_STACK_OPS)],
+            codes("1 dup drop\n1 2 swap # swapped twice # swap print print")
+        );
+    }
+
+    #[test]
+    fn checker_warnings_have_codes() {
+        let src = "fdef f : number \"a\" fend f print\n1 1 2 < if drop end";
+
+        assert_eq!(
+            vec![(1, RETURN_TYPE_MISMATCH), (2, UNBALANCED_IF)],
+            codes(src)
+        );
+    }
+
+    #[test]
+    fn suppression() {
+        let src = "\
+# lint-allow L001 #
+fdef unused : ! fend
+fdef also_unused : ! fend # lint-allow L001 #
+fdef reported : ! fend
+1 dup drop # lint-allow L001 #";
+
+        assert_eq!(
+            vec![(4, UNUSED_FUNCTION), (5, REDUNDANT_STACK_OPS)],
+            codes(src)
+        );
+    }
+}
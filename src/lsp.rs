@@ -0,0 +1,579 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    built_in_words, checker,
+    diagnostics::{Diagnostic, Severity},
+    doc,
+    lexer::{ILToken, Lexer, Span},
+};
+
+const KEYWORDS: &[&str] = &["fdef", "fend", "if", "end", "true", "false"];
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// NOTE: Values from the LSP specification
+const SYNC_FULL: u64 = 1;
+const SYMBOL_FUNCTION: u64 = 12;
+const COMPLETION_FUNCTION: u64 = 3;
+const COMPLETION_KEYWORD: u64 = 14;
+
+/// Open document and everything known about it
+struct Document {
+    text: String,
+    tokens: Vec<ILToken>,
+    spans: Vec<Span>,
+    diagnostics: Vec<Diagnostic>,
+    effects: HashMap<String, checker::Effect>,
+    /// Doc comment of every documented function
+    docs: HashMap<String, String>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut lexer = Lexer::new(&text, false);
+        let (tokens, spans) = lexer.tokenize();
+        let (diagnostics, effects) = match lexer.error() {
+            Some((e, span)) => (vec![Diagnostic::error(*span, e.summary())], HashMap::new()),
+            None => {
+                let report = checker::check(&tokens, &spans);
+                (report.diagnostics, report.effects)
+            }
+        };
+        let docs = doc::functions(&tokens, &spans)
+            .into_iter()
+            .filter(|word| !word.doc.is_empty())
+            .map(|word| (word.name, word.doc))
+            .collect();
+
+        Self {
+            text,
+            tokens,
+            spans,
+            diagnostics,
+            effects,
+            docs,
+        }
+    }
+
+    /// Converts a byte offset into an LSP position, which counts UTF-16 code
+    /// units
+    fn position(&self, offset: usize) -> Value {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+        json!({ "line": line, "character": character })
+    }
+
+    fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+        let line_start = if line == 0 {
+            0
+        } else {
+            match self.text.match_indices('\n').nth(line - 1) {
+                Some((i, _)) => i + 1,
+                None => return self.text.len(),
+            }
+        };
+        let mut units = 0;
+        for (i, c) in self.text[line_start..].char_indices() {
+            if units >= character || c == '\n' {
+                return line_start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    fn range(&self, start: usize, end: usize) -> Value {
+        json!({ "start": self.position(start), "end": self.position(end) })
+    }
+
+    /// Index of the token under the cursor
+    fn token_at(&self, offset: usize) -> Option<usize> {
+        self.spans
+            .iter()
+            .position(|span| span.start <= offset && offset <= span.end)
+    }
+
+    fn definition(&self, name: &str) -> Option<usize> {
+        self.tokens
+            .iter()
+            .position(|token| matches!(token, ILToken::FuncDef(info) if info.name == name))
+    }
+
+    /// Name of the word under the cursor, either a call or a function header
+    fn word_at(&self, offset: usize) -> Option<&str> {
+        match &self.tokens[self.token_at(offset)?] {
+            ILToken::Symbol(name) | ILToken::TailCall(name) => Some(name),
+            ILToken::FuncDef(info) => Some(&info.name),
+            _ => None,
+        }
+    }
+
+    /// Pairs of `fdef` and matching `fend` token indices
+    fn functions(&self) -> Vec<(usize, usize)> {
+        let mut functions = vec![];
+        let mut start = None;
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token {
+                ILToken::FuncDef(_) => start = Some(i),
+                ILToken::FuncEnd => {
+                    if let Some(start) = start.take() {
+                        functions.push((start, i));
+                    }
+                }
+                _ => {}
+            }
+        }
+        functions
+    }
+}
+
+/// Language server speaking JSON-RPC with `Content-Length` framing
+pub struct Server {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    documents: HashMap<String, Document>,
+}
+
+impl Server {
+    pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Self {
+            input,
+            output,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Reads the next message, `None` once the input is closed
+    fn read_message(&mut self) -> io::Result<Option<Value>> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(length) = length else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Missing Content-Length header",
+            ));
+        };
+        let mut body = vec![0; length];
+        self.input.read_exact(&mut body)?;
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(io::Error::from)
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics: Vec<Value> = match self.documents.get(uri) {
+            Some(document) => document
+                .diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    json!({
+                        "range": document.range(diagnostic.span.start, diagnostic.span.end),
+                        "severity": match diagnostic.severity {
+                            Severity::Error => 1,
+                            Severity::Warning => 2,
+                        },
+                        "source": "iodine",
+                        "code": diagnostic.code,
+                        "message": diagnostic.message,
+                    })
+                })
+                .collect(),
+            None => vec![],
+        };
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents
+                    .insert(uri.clone(), Document::new(text.to_string()));
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                // NOTE: Only full document sync is advertised, the last change
+                // holds the whole text
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    self.documents
+                        .insert(uri.clone(), Document::new(text.to_string()));
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let offset = document.offset(&params["position"]);
+        let Some(def) = document
+            .word_at(offset)
+            .and_then(|name| document.definition(name))
+        else {
+            return Value::Null;
+        };
+        let span = document.spans[def];
+        json!({ "uri": uri, "range": document.range(span.start, span.end) })
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let offset = document.offset(&params["position"]);
+        let Some(name) = document.word_at(offset) else {
+            return Value::Null;
+        };
+
+        let contents = if let Some(def) = document.definition(name) {
+            let ILToken::FuncDef(info) = &document.tokens[def] else {
+                unreachable!()
+            };
+            let mut contents = format!("```iodine\nfdef {}\n```", info);
+            if let Some(effect) = document.effects.get(name) {
+                contents.push_str(&format!("\nStack effect: `{}`", effect));
+            }
+            if let Some(doc) = document.docs.get(name) {
+                contents.push_str(&format!("\n\n{}", doc));
+            }
+            contents
+        } else if let Some(builtin) = built_in_words::find_builtin(name) {
+            format!(
+                "```iodine\n{} {}\n```\n{}",
+                builtin.name, builtin.signature, builtin.description
+            )
+        } else {
+            return Value::Null;
+        };
+        json!({ "contents": { "kind": "markdown", "value": contents } })
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let mut items: Vec<Value> = built_in_words::BUILTINS
+            .iter()
+            .map(|builtin| {
+                json!({
+                    "label": builtin.name,
+                    "kind": COMPLETION_FUNCTION,
+                    "detail": builtin.signature,
+                    "documentation": builtin.description,
+                })
+            })
+            .collect();
+
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        if let Some(document) = self.documents.get(uri) {
+            for token in &document.tokens {
+                if let ILToken::FuncDef(info) = token {
+                    items.push(json!({
+                        "label": info.name,
+                        "kind": COMPLETION_FUNCTION,
+                        "detail": format!("fdef {}", info),
+                    }));
+                }
+            }
+        }
+
+        items.extend(
+            KEYWORDS
+                .iter()
+                .map(|keyword| json!({ "label": keyword, "kind": COMPLETION_KEYWORD })),
+        );
+        Value::Array(items)
+    }
+
+    fn document_symbol(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let symbols = document
+            .functions()
+            .into_iter()
+            .filter_map(|(start, end)| {
+                let ILToken::FuncDef(info) = &document.tokens[start] else {
+                    return None;
+                };
+                let header = document.spans[start];
+                Some(json!({
+                    "name": info.name,
+                    "detail": format!(": {}", info.return_type),
+                    "kind": SYMBOL_FUNCTION,
+                    "range": document.range(header.start, document.spans[end].end),
+                    "selectionRange": document.range(header.start, header.end),
+                }))
+            })
+            .collect();
+        Value::Array(symbols)
+    }
+
+    fn request(&mut self, id: Value, method: &str, params: &Value) -> io::Result<()> {
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "iodine", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.document_symbol(params),
+            _ => {
+                return self.send(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("Unknown method: {}", method),
+                    },
+                }))
+            }
+        };
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Serves requests until `exit` or the end of the input
+    pub fn run(&mut self) -> io::Result<()> {
+        while let Some(message) = self.read_message()? {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                break;
+            }
+            match message.get("id") {
+                Some(id) => self.request(id.clone(), method, &message["params"])?,
+                None => self.notification(method, &message["params"])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::SharedBuffer;
+
+    /// Runs the server on a scripted session and returns every message it sent
+    fn session(messages: &[Value]) -> Vec<Value> {
+        let mut input = String::new();
+        for message in messages {
+            let body = message.to_string();
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        }
+
+        let output = SharedBuffer::default();
+        let mut server = Server::new(
+            Box::new(io::Cursor::new(input.into_bytes())),
+            Box::new(output.clone()),
+        );
+        server.run().unwrap();
+
+        let mut replies = vec![];
+        let contents = output.contents();
+        let mut rest = contents.as_str();
+        while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+            let length: usize = header
+                .trim_start_matches("Content-Length: ")
+                .parse()
+                .unwrap();
+            replies.push(serde_json::from_str(&body[..length]).unwrap());
+            rest = &body[length..];
+        }
+        replies
+    }
+
+    const URI: &str = "file:///main.iod";
+    const SRC: &str = "fdef square : number\n    dup *\nfend\n\n3 square print\n## Doubles ##\nfdef double : number 2 * fend\n";
+
+    fn open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "iodine", "version": 1, "text": text } },
+        })
+    }
+
+    fn request(id: u64, method: &str, line: u64, character: u64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            },
+        })
+    }
+
+    #[test]
+    fn initialize_and_shutdown() {
+        let replies = session(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+        ]);
+
+        assert_eq!(2, replies.len());
+        assert_eq!(true, replies[0]["result"]["capabilities"]["hoverProvider"]);
+        assert_eq!(Value::Null, replies[1]["result"]);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let replies = session(&[open("1 +\n'a' 2 *"), open("1 2 6942O 3")]);
+
+        assert_eq!("textDocument/publishDiagnostics", replies[0]["method"]);
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(2, diagnostics.as_array().unwrap().len());
+        assert_eq!(
+            json!({ "start": { "line": 0, "character": 2 }, "end": { "line": 0, "character": 3 } }),
+            diagnostics[0]["range"]
+        );
+        assert_eq!(1, diagnostics[1]["severity"]);
+        assert_eq!(
+            "`*` expects number but got string",
+            diagnostics[1]["message"]
+        );
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(1, diagnostics.as_array().unwrap().len());
+        assert_eq!(4, diagnostics[0]["range"]["start"]["character"]);
+    }
+
+    #[test]
+    fn definition_and_hover() {
+        let replies = session(&[
+            open(SRC),
+            request(1, "textDocument/definition", 4, 4),
+            request(2, "textDocument/hover", 4, 4),
+            request(3, "textDocument/hover", 1, 5),
+            request(4, "textDocument/hover", 4, 0),
+            request(5, "textDocument/hover", 6, 6),
+        ]);
+
+        assert_eq!(
+            json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 20 } }),
+            replies[1]["result"]["range"]
+        );
+        assert_eq!(
+            "```iodine\nfdef square : number\n```\nStack effect: `( any -- number )`",
+            replies[2]["result"]["contents"]["value"]
+        );
+        assert_eq!(
+            "```iodine\ndup ( a -- a a )\n```\nDuplicates the top value of the stack",
+            replies[3]["result"]["contents"]["value"]
+        );
+        assert_eq!(Value::Null, replies[4]["result"]);
+        assert_eq!(
+            "```iodine\nfdef double : number\n```\nStack effect: `( number -- number )`\n\nDoubles",
+            replies[5]["result"]["contents"]["value"]
+        );
+    }
+
+    #[test]
+    fn completion_and_symbols() {
+        let replies = session(&[
+            open(SRC),
+            request(1, "textDocument/completion", 4, 0),
+            request(2, "textDocument/documentSymbol", 0, 0),
+            request(3, "textDocument/formatting", 0, 0),
+        ]);
+
+        let labels: Vec<&str> = replies[1]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"print"));
+        assert!(labels.contains(&"square"));
+        assert!(labels.contains(&"fdef"));
+
+        let symbols = &replies[2]["result"];
+        assert_eq!(2, symbols.as_array().unwrap().len());
+        assert_eq!("square", symbols[0]["name"]);
+        assert_eq!(SYMBOL_FUNCTION, symbols[0]["kind"]);
+        assert_eq!(2, symbols[0]["range"]["end"]["line"]);
+
+        assert_eq!(METHOD_NOT_FOUND, replies[3]["error"]["code"]);
+    }
+
+    #[test]
+    fn utf16_positions() {
+        let document = Document::new("'é😀' print".to_string());
+        let offset = "'é😀' ".len();
+
+        assert_eq!(
+            json!({ "line": 0, "character": 6 }),
+            document.position(offset)
+        );
+        assert_eq!(
+            offset,
+            document.offset(&json!({ "line": 0, "character": 6 }))
+        );
+    }
+}
@@ -1,17 +1,36 @@
+mod bigint;
 mod built_in_words;
+mod capabilities;
+mod checker;
+mod codegen;
+mod constants;
+mod debugger;
+mod diagnostics;
+mod disasm;
+mod doc;
+mod emit_c;
 mod errors;
+mod formatter;
 mod interpreter;
 mod lexer;
+mod lint;
+mod lsp;
+mod profiler;
+mod runtime;
 mod stack;
+mod test_runner;
+#[cfg(test)]
+mod test_utils;
+mod trace;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
     /// Name of the source file to compile
-    #[arg(short)]
-    source_file: String,
+    #[arg(short, required = true)]
+    source_file: Option<String>,
 
     /// Time the parsing, tokenization
     #[arg(long, default_value_t = false)]
@@ -21,28 +40,446 @@ struct Args {
     /// For debug purposes
     #[arg(long, default_value_t = false)]
     only_parse: bool,
+
+    /// Log every executed token and the stack after it
+    #[arg(long, default_value_t = false)]
+    trace: bool,
+
+    /// Write the trace to a file instead of stderr
+    #[arg(long, requires = "trace")]
+    trace_file: Option<String>,
+
+    /// Format of the trace
+    #[arg(long, requires = "trace", value_enum, default_value_t = trace::TraceFormat::Text)]
+    trace_format: trace::TraceFormat,
+
+    /// Report calls, time and instructions per function and builtin word
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+
+    /// Also write the call stacks in the folded format used by flame graph tools
+    #[arg(long, requires = "profile")]
+    profile_folded: Option<String>,
+
+    /// Stop the program after this many instructions
+    #[arg(long)]
+    max_instructions: Option<u64>,
+
+    /// Stop the program once the stack holds more values than this
+    #[arg(long)]
+    max_stack: Option<usize>,
+
+    /// Stop the program once calls are nested deeper than this
+    #[arg(long)]
+    max_calls: Option<usize>,
+
+    /// Stop the program once a string (in bytes), a list or an integer (in digits) gets longer
+    /// than this
+    #[arg(long)]
+    max_size: Option<usize>,
+
+    /// Stop the program after running for this many milliseconds
+    #[arg(long)]
+    timeout: Option<u64>,
+
+    /// Deny the program any access to the host, except what is given with `--allow`
+    #[arg(long, default_value_t = false)]
+    sandbox: bool,
+
+    /// Capability granted to a sandboxed program, can be repeated
+    #[arg(long, requires = "sandbox", value_enum)]
+    allow: Vec<capabilities::Capability>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() {
-    let args = Args::parse();
-    let source = match std::fs::read_to_string(args.source_file) {
-        Ok(str) => str,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the compiled program one instruction per line
+    Disasm {
+        /// Name of the source file to disassemble
+        source_file: String,
+    },
+    /// Compile the program to a native x86-64 Linux executable
+    Build {
+        /// Name of the source file to compile
+        source_file: String,
+
+        /// Path of the produced executable, the assembly is written next to it
+        #[arg(short)]
+        output: Option<String>,
+    },
+    /// Step through the program in an interactive debugger
+    Debug {
+        /// Name of the source file to debug
+        source_file: String,
+
+        /// File used as the program's input, the debugger commands are read from stdin
+        #[arg(long)]
+        input: Option<String>,
+    },
+    /// Reformat source files in place into the canonical style
+    Fmt {
+        /// Files to format
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Do not write anything, exit with an error if a file is not formatted
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+    /// Translate the program to a standalone C file
+    EmitC {
+        /// Name of the source file to translate
+        source_file: String,
+
+        /// Path of the produced C file, printed to stdout if not given
+        #[arg(short)]
+        output: Option<String>,
+    },
+    /// Report common stack mistakes and suspicious code
+    Lint {
+        /// Files to check
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Run a language server over stdin and stdout
+    Lsp,
+    /// Generate reference pages for the functions of source files and for
+    /// the builtin words
+    Doc {
+        /// Files to document
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Directory the pages are written to
+        #[arg(short, default_value = "docs")]
+        output: String,
+
+        /// Format of the pages
+        #[arg(long, value_enum, default_value_t = doc::DocFormat::Markdown)]
+        format: doc::DocFormat,
+    },
+    /// Run the `test` blocks of source files
+    Test {
+        /// Files or directories searched for `.iod` files
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+    },
+}
+
+fn read_source(path: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(str) => Some(str),
         Err(e) => {
             eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// Parses the source, exits if it has an error. The lexer already printed it
+fn parse(source: &str, time: bool) -> (Vec<lexer::ILToken>, Vec<lexer::Span>) {
+    let mut lexer = lexer::Lexer::new(source, time);
+    let parsed = lexer.parse_with_spans();
+    if lexer.error().is_some() {
+        std::process::exit(1);
+    }
+    parsed
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match &args.command {
+        Some(Command::Disasm { source_file }) => {
+            let Some(source) = read_source(source_file) else {
+                return;
+            };
+            let (tokens, spans) = parse(&source, args.time);
+            if let Err(e) = disasm::disassemble(&tokens, &spans, &mut std::io::stdout()) {
+                eprintln!("{}", e);
+            }
             return;
         }
+        Some(Command::Build {
+            source_file,
+            output,
+        }) => {
+            let Some(source) = read_source(source_file) else {
+                return;
+            };
+            let output = match output {
+                Some(output) => std::path::PathBuf::from(output),
+                None => std::path::Path::new(source_file).with_extension(""),
+            };
+            let (tokens, _) = parse(&source, args.time);
+            if let Err(e) = codegen::build(&tokens, &output) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::EmitC {
+            source_file,
+            output,
+        }) => {
+            let Some(source) = read_source(source_file) else {
+                return;
+            };
+            let (tokens, _) = parse(&source, args.time);
+            let c = match emit_c::emit(&tokens) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            match output {
+                Some(output) => {
+                    if let Err(e) = std::fs::write(output, c) {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{}", c),
+            }
+            return;
+        }
+        Some(Command::Debug { source_file, input }) => {
+            let Some(source) = read_source(source_file) else {
+                return;
+            };
+            let input: Option<Box<dyn std::io::BufRead>> = match input {
+                Some(path) => match std::fs::File::open(path) {
+                    Ok(file) => Some(Box::new(std::io::BufReader::new(file))),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            let (tokens, spans) = parse(&source, args.time);
+            let interpreter =
+                interpreter::Interpreter::with_tokens(tokens, spans, None, input, false);
+            let mut debugger = debugger::Debugger::new(
+                interpreter,
+                Box::new(std::io::BufReader::new(std::io::stdin())),
+                Box::new(std::io::stdout()),
+            );
+            if let Err(e) = debugger.run() {
+                eprintln!("{}", e);
+            }
+            return;
+        }
+        Some(Command::Fmt { files, check }) => {
+            let mut failed = false;
+            for file in files {
+                let Some(source) = read_source(file) else {
+                    failed = true;
+                    continue;
+                };
+                let formatted = match formatter::format(&source) {
+                    Ok(formatted) => formatted,
+                    Err(e) => {
+                        eprintln!("{}: {}", file, e);
+                        failed = true;
+                        continue;
+                    }
+                };
+                if formatted == source {
+                    continue;
+                }
+                if *check {
+                    eprintln!("Would reformat: {}", file);
+                    failed = true;
+                } else if let Err(e) = std::fs::write(file, formatted) {
+                    eprintln!("{}: {}", file, e);
+                    failed = true;
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Lsp) => {
+            let mut server = lsp::Server::new(
+                Box::new(std::io::BufReader::new(std::io::stdin())),
+                Box::new(std::io::stdout()),
+            );
+            if let Err(e) = server.run() {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Lint { files }) => {
+            let mut failed = false;
+            for file in files {
+                let Some(source) = read_source(file) else {
+                    failed = true;
+                    continue;
+                };
+                for diagnostic in lint::lint(&source) {
+                    println!("{}: {}", file, diagnostic);
+                    failed = true;
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Doc {
+            files,
+            output,
+            format,
+        }) => {
+            let output = std::path::Path::new(output);
+            if let Err(e) = std::fs::create_dir_all(output) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            let mut pages = vec![(
+                "builtins".to_string(),
+                doc::render("Builtin words", &doc::builtins(), *format),
+            )];
+            for file in files {
+                let Some(source) = read_source(file) else {
+                    std::process::exit(1);
+                };
+                let mut lexer = lexer::Lexer::new(&source, args.time);
+                let (tokens, spans) = lexer.tokenize();
+                if let Some((e, _)) = lexer.error() {
+                    eprintln!("{}: {}", file, e);
+                    std::process::exit(1);
+                }
+                let name = std::path::Path::new(file)
+                    .file_stem()
+                    .map_or(file.clone(), |stem| stem.to_string_lossy().into_owned());
+                // NOTE: Refuse rather than overwrite the builtin page or the page
+                // of a source file with the same name in another directory
+                if pages.iter().any(|(page, _)| *page == name) {
+                    eprintln!(
+                        "{}: its page `{}.{}` would overwrite another page",
+                        file,
+                        name,
+                        format.extension()
+                    );
+                    std::process::exit(1);
+                }
+                let words = doc::functions(&tokens, &spans);
+                pages.push((name.clone(), doc::render(&name, &words, *format)));
+            }
+            for (name, page) in pages {
+                let path = output.join(name).with_extension(format.extension());
+                if let Err(e) = std::fs::write(&path, page) {
+                    eprintln!("{}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Test { paths }) => {
+            let summary = test_runner::collect_files(paths)
+                .and_then(|files| test_runner::run(&files, &mut std::io::stdout()));
+            match summary {
+                Ok(summary) if summary.failed == 0 => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
+    }
+
+    let Some(source) = read_source(&args.source_file.unwrap()) else {
+        return;
     };
 
     if args.only_parse {
-        let lexer = lexer::Lexer::new(&source, args.time);
+        let mut lexer = lexer::Lexer::new(&source, args.time);
         eprintln!("{:?}", lexer.parse());
         return;
     }
 
-    let mut interpreter =
-        interpreter::Interpreter::new(lexer::Lexer::new(&source, args.time), None, None, args.time);
-    match interpreter.run() {
-        Ok(()) => {}
-        Err(e) => eprintln!("{}", e),
+    let capabilities = if args.sandbox {
+        args.allow.iter().fold(
+            capabilities::Capabilities::none(),
+            |capabilities, capability| capabilities.with(*capability),
+        )
+    } else {
+        capabilities::Capabilities::all()
+    };
+    let (tokens, spans) = parse(&source, args.time);
+    let mut interpreter = interpreter::Interpreter::with_capabilities(
+        tokens,
+        spans,
+        None,
+        None,
+        args.time,
+        capabilities,
+    );
+
+    if args.trace {
+        let output: Box<dyn std::io::Write> = match &args.trace_file {
+            Some(path) => match std::fs::File::create(path) {
+                Ok(file) => Box::new(std::io::BufWriter::new(file)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            },
+            None => Box::new(std::io::stderr()),
+        };
+        interpreter.set_tracer(trace::Tracer::new(output, args.trace_format));
+    }
+    if args.profile {
+        interpreter.set_profiler(profiler::Profiler::new());
+    }
+    interpreter.set_limits(interpreter::Limits {
+        instructions: args.max_instructions,
+        stack_depth: args.max_stack,
+        call_depth: args.max_calls,
+        value_size: args.max_size,
+        deadline: args.timeout.map(std::time::Duration::from_millis),
+    });
+
+    let result = interpreter.run();
+    if let Err(e) = &result {
+        eprintln!("Error on line {}: {}", interpreter.current_line(), e);
+    }
+    // NOTE: `process::exit` doesn't run destructors, the trace file would be
+    // left unflushed
+    if let Some(mut tracer) = interpreter.take_tracer() {
+        if let Err(e) = tracer.flush() {
+            eprintln!("{}", e);
+        }
+    }
+
+    if let Some(profiler) = interpreter.take_profiler() {
+        if let Err(e) = profiler.report(&mut std::io::stderr()) {
+            eprintln!("{}", e);
+        }
+        if let Some(path) = &args.profile_folded {
+            let result =
+                std::fs::File::create(path).and_then(|mut file| profiler.write_folded(&mut file));
+            if let Err(e) = result {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
+    if let Some(code) = interpreter.exit_code() {
+        std::process::exit(code);
     }
 }
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Stats {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+    instructions: u64,
+}
+
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    start: Instant,
+    children: Duration,
+    instructions: u64,
+}
+
+impl Frame {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            start: Instant::now(),
+            children: Duration::ZERO,
+            instructions: 0,
+        }
+    }
+}
+
+/// Collects call counts, timings and instruction counts per `fdef` function
+/// and per builtin word. Top level code is accounted to a `<top level>`
+/// frame, a name no function can have
+#[derive(Debug)]
+pub struct Profiler {
+    functions: HashMap<String, Stats>,
+    builtins: HashMap<String, Stats>,
+    /// Exclusive time spent in every distinct call stack, for flame graphs
+    folded: HashMap<String, Duration>,
+    frames: Vec<Frame>,
+    instructions: u64,
+}
+
+const TOP_LEVEL: &str = "<top level>";
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+            builtins: HashMap::new(),
+            folded: HashMap::new(),
+            frames: vec![Frame::new(TOP_LEVEL)],
+            instructions: 0,
+        }
+    }
+
+    fn call_stack(&self) -> String {
+        let names: Vec<&str> = self.frames.iter().map(|f| f.name.as_str()).collect();
+        names.join(";")
+    }
+
+    /// Counts a single executed token
+    pub fn instruction(&mut self) {
+        self.instructions += 1;
+        if let Some(frame) = self.frames.last_mut() {
+            frame.instructions += 1;
+        }
+    }
+
+    /// Records a builtin word that ran for `elapsed`
+    pub fn builtin(&mut self, name: &str, elapsed: Duration) {
+        let stats = self.builtins.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.inclusive += elapsed;
+        stats.exclusive += elapsed;
+        stats.instructions += 1;
+
+        let path = format!("{};{}", self.call_stack(), name);
+        *self.folded.entry(path).or_default() += elapsed;
+        if let Some(frame) = self.frames.last_mut() {
+            frame.children += elapsed;
+        }
+    }
+
+    /// Called when the function `name` is entered
+    pub fn enter(&mut self, name: &str) {
+        self.frames.push(Frame::new(name));
+    }
+
+    /// Called when the innermost function returns
+    pub fn exit(&mut self) {
+        // NOTE: The `main` frame is only closed by `finish`
+        if self.frames.len() > 1 {
+            self.close_frame();
+        }
+    }
+
+    fn close_frame(&mut self) {
+        let path = self.call_stack();
+        let frame = self.frames.pop().unwrap();
+        let inclusive = frame.start.elapsed();
+        let exclusive = inclusive.saturating_sub(frame.children);
+
+        *self.folded.entry(path).or_default() += exclusive;
+        if let Some(parent) = self.frames.last_mut() {
+            parent.children += inclusive;
+        }
+
+        let stats = self.functions.entry(frame.name).or_default();
+        stats.calls += 1;
+        stats.inclusive += inclusive;
+        stats.exclusive += exclusive;
+        stats.instructions += frame.instructions;
+    }
+
+    /// Closes every open frame, called once the program ends
+    pub fn finish(&mut self) {
+        while !self.frames.is_empty() {
+            self.close_frame();
+        }
+    }
+
+    fn write_table(
+        out: &mut dyn Write,
+        title: &str,
+        stats: &HashMap<String, Stats>,
+    ) -> io::Result<()> {
+        let mut rows: Vec<(&String, &Stats)> = stats.iter().collect();
+        rows.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+
+        writeln!(
+            out,
+            "{:<24} {:>10} {:>14} {:>14} {:>14}",
+            title, "calls", "inclusive", "exclusive", "instructions"
+        )?;
+        for (name, stats) in rows {
+            writeln!(
+                out,
+                "{:<24} {:>10} {:>14} {:>14} {:>14}",
+                name,
+                stats.calls,
+                format!("{:.2?}", stats.inclusive),
+                format!("{:.2?}", stats.exclusive),
+                stats.instructions
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the report sorted by exclusive time
+    pub fn report(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "Instructions executed: {}", self.instructions)?;
+        writeln!(out)?;
+        Self::write_table(out, "function", &self.functions)?;
+        writeln!(out)?;
+        Self::write_table(out, "builtin", &self.builtins)
+    }
+
+    /// Writes the call stacks in the folded format understood by
+    /// `flamegraph.pl` and similar tools, weighted in nanoseconds
+    pub fn write_folded(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut stacks: Vec<(&String, &Duration)> = self.folded.iter().collect();
+        stacks.sort();
+        for (stack, time) in stacks {
+            writeln!(out, "{} {}", stack, time.as_nanos())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, lexer::Lexer};
+
+    fn profile(src: &str) -> Profiler {
+        let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+        interpreter.set_profiler(Profiler::new());
+        interpreter.run().unwrap();
+        interpreter.take_profiler().unwrap()
+    }
+
+    #[test]
+    fn call_counts() {
+        let profiler = profile(
+            "fdef square : number dup * fend \
+             fdef fourth : number square square fend \
+             2 fourth 3 square",
+        );
+
+        // NOTE: The second `square` is a tail call, `fourth` never runs its `fend`
+        assert_eq!(17, profiler.instructions);
+        assert_eq!(1, profiler.functions[TOP_LEVEL].calls);
+        assert_eq!(6, profiler.functions[TOP_LEVEL].instructions);
+        assert_eq!(1, profiler.functions["fourth"].calls);
+        assert_eq!(2, profiler.functions["fourth"].instructions);
+        assert_eq!(3, profiler.functions["square"].calls);
+        assert_eq!(9, profiler.functions["square"].instructions);
+        assert_eq!(3, profiler.builtins["dup"].calls);
+        assert_eq!(3, profiler.builtins["*"].calls);
+    }
+
+    #[test]
+    fn failing_program() {
+        let mut interpreter = Interpreter::new(
+            Lexer::new("fdef f : ! 1 frobnicate fend f", false),
+            None,
+            None,
+            false,
+        );
+        interpreter.set_profiler(Profiler::new());
+        assert!(interpreter.run().is_err());
+        let profiler = interpreter.take_profiler().unwrap();
+
+        assert_eq!(1, profiler.functions[TOP_LEVEL].calls);
+        assert_eq!(1, profiler.functions["f"].calls);
+        assert_eq!(2, profiler.functions["f"].instructions);
+    }
+
+    #[test]
+    fn function_named_main() {
+        let profiler = profile("fdef main : ! 1 drop fend main");
+
+        assert_eq!(1, profiler.functions["main"].calls);
+        assert_eq!(3, profiler.functions["main"].instructions);
+        assert_eq!(1, profiler.functions[TOP_LEVEL].calls);
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let profiler = profile("fdef f : ! 1 drop fend f f");
+        let mut out = vec![];
+        profiler.write_folded(&mut out).unwrap();
+        let stacks: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0.to_string())
+            .collect();
+
+        assert_eq!(
+            vec!["<top level>", "<top level>;f", "<top level>;f;drop"],
+            stacks
+        );
+    }
+}
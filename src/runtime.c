@@ -0,0 +1,1537 @@
+/*
+ * Runtime for compiled Iodine programs.
+ *
+ * Mirrors `StackValue` and the builtins in `built_in_words.rs`, the
+ * interpreter is the reference implementation for everything in here.
+ */
+#include <errno.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+#define IOD_STACK_SIZE (1 << 20)
+#define IOD_FRAMES_SIZE (1 << 16)
+#define IOD_VARIABLES_SIZE (1 << 16)
+
+typedef enum {
+    IOD_STRING,
+    IOD_UNSIGNED_INT,
+    IOD_SIGNED_INT,
+    IOD_BIG_INT,
+    IOD_FLOAT,
+    IOD_BOOL,
+    IOD_LIST,
+    IOD_QUOTATION,
+} iod_tag;
+
+typedef struct iod_bigint iod_bigint;
+typedef struct iod_list iod_list;
+typedef struct iod_quotation iod_quotation;
+
+typedef struct {
+    iod_tag tag;
+    union {
+        const char *string;
+        uint64_t unsigned_int;
+        int64_t signed_int;
+        iod_bigint *big_int;
+        double flt;
+        int boolean;
+        iod_list *list;
+        iod_quotation *quotation;
+    } as;
+} iod_value;
+
+/* Integer of any size, used once a value doesn't fit in 64 bits. Same as
+ * `BigInt`: base 2^32 digits, least significant first, without leading
+ * zeros. Zero has no digits and is never negative */
+struct iod_bigint {
+    int negative;
+    size_t length;
+    uint32_t *digits;
+};
+
+/* Lists, quotations and big integers on the stack are never freed, same as
+ * strings read from stdin */
+struct iod_list {
+    size_t length;
+    iod_value *items;
+};
+
+/* Code of a `[ ... ]` block together with the locals it captured */
+struct iod_quotation {
+    void (*code)(void);
+    size_t count;
+    iod_value *locals;
+};
+
+static iod_value iod_stack[IOD_STACK_SIZE];
+static size_t iod_sp = 0;
+
+static void iod_fail(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "%s\n", message);
+    exit(1);
+}
+
+static void iod_push(iod_value value) {
+    if (iod_sp == IOD_STACK_SIZE) {
+        iod_fail("Stack overflow during execution");
+    }
+    iod_stack[iod_sp++] = value;
+}
+
+static iod_value iod_pop(void) {
+    if (iod_sp == 0) {
+        iod_fail("Empty stack during execution");
+    }
+    return iod_stack[--iod_sp];
+}
+
+void iod_push_string(const char *value) {
+    iod_value v = {.tag = IOD_STRING, .as.string = value};
+    iod_push(v);
+}
+
+void iod_push_unsigned_int(uint64_t value) {
+    iod_value v = {.tag = IOD_UNSIGNED_INT, .as.unsigned_int = value};
+    iod_push(v);
+}
+
+void iod_push_signed_int(int64_t value) {
+    iod_value v = {.tag = IOD_SIGNED_INT, .as.signed_int = value};
+    iod_push(v);
+}
+
+void iod_push_float(double value) {
+    iod_value v = {.tag = IOD_FLOAT, .as.flt = value};
+    iod_push(v);
+}
+
+void iod_push_bool(int value) {
+    iod_value v = {.tag = IOD_BOOL, .as.boolean = value != 0};
+    iod_push(v);
+}
+
+static iod_bigint iod_bigint_new(int negative, uint32_t *digits, size_t length) {
+    while (length > 0 && digits[length - 1] == 0) {
+        length--;
+    }
+    iod_bigint value = {.negative = negative && length > 0, .length = length, .digits = digits};
+    return value;
+}
+
+/* The digits are written to `buffer`, which needs room for 4 of them */
+static iod_bigint iod_bigint_from_int128(__int128 value, uint32_t *buffer) {
+    unsigned __int128 rest = value < 0 ? -(unsigned __int128)value : (unsigned __int128)value;
+    for (int i = 0; i < 4; i++) {
+        buffer[i] = (uint32_t)rest;
+        rest >>= 32;
+    }
+    return iod_bigint_new(value < 0, buffer, 4);
+}
+
+/* Returns 0 if the value doesn't fit in 128 bits */
+static int iod_bigint_to_int128(const iod_bigint *value, __int128 *result) {
+    if (value->length > 4) {
+        return 0;
+    }
+    unsigned __int128 magnitude = 0;
+    for (size_t i = value->length; i > 0; i--) {
+        magnitude = (magnitude << 32) | value->digits[i - 1];
+    }
+    unsigned __int128 limit = (unsigned __int128)1 << 127;
+    if (value->negative ? magnitude > limit : magnitude >= limit) {
+        return 0;
+    }
+    *result = value->negative ? -(__int128)(magnitude - 1) - 1 : (__int128)magnitude;
+    return 1;
+}
+
+/* Exact value of a finite float without a fractional part, the result owns
+ * its digits */
+static iod_bigint iod_bigint_from_integral(double value) {
+    int exponent;
+    double fraction = frexp(fabs(value), &exponent);
+    /* `|value|` is the 53 bit mantissa shifted left by `shift` */
+    uint64_t mantissa = (uint64_t)ldexp(fraction, 53);
+    int shift = exponent - 53;
+    if (shift <= 0) {
+        return iod_bigint_from_int128(value, malloc(4 * sizeof(uint32_t)));
+    }
+    size_t length = (size_t)shift / 32 + 3;
+    uint32_t *digits = calloc(length, sizeof(uint32_t));
+    unsigned __int128 shifted = (unsigned __int128)mantissa << (shift % 32);
+    for (size_t i = length - 3; i < length; i++) {
+        digits[i] = (uint32_t)shifted;
+        shifted >>= 32;
+    }
+    return iod_bigint_new(value < 0, digits, length);
+}
+
+static int iod_compare_magnitudes(const iod_bigint *a, const iod_bigint *b) {
+    if (a->length != b->length) {
+        return a->length > b->length ? 1 : -1;
+    }
+    for (size_t i = a->length; i > 0; i--) {
+        if (a->digits[i - 1] != b->digits[i - 1]) {
+            return a->digits[i - 1] > b->digits[i - 1] ? 1 : -1;
+        }
+    }
+    return 0;
+}
+
+static int iod_bigint_compare(const iod_bigint *a, const iod_bigint *b) {
+    if (a->negative != b->negative) {
+        return a->negative ? -1 : 1;
+    }
+    int ordering = iod_compare_magnitudes(a, b);
+    return a->negative ? -ordering : ordering;
+}
+
+/* Results of the arithmetic own newly allocated digits */
+static iod_bigint iod_bigint_add(const iod_bigint *a, const iod_bigint *b) {
+    size_t length = (a->length > b->length ? a->length : b->length) + 1;
+    uint32_t *digits = malloc(length * sizeof(uint32_t));
+    if (a->negative == b->negative) {
+        uint64_t carry = 0;
+        for (size_t i = 0; i < length; i++) {
+            uint64_t current = carry + (i < a->length ? a->digits[i] : 0) +
+                               (i < b->length ? b->digits[i] : 0);
+            digits[i] = (uint32_t)current;
+            carry = current >> 32;
+        }
+        return iod_bigint_new(a->negative, digits, length);
+    }
+
+    /* The larger magnitude gives the sign, the smaller one is subtracted */
+    if (iod_compare_magnitudes(a, b) < 0) {
+        const iod_bigint *larger = b;
+        b = a;
+        a = larger;
+    }
+    int64_t borrow = 0;
+    for (size_t i = 0; i < length; i++) {
+        int64_t current = (int64_t)(i < a->length ? a->digits[i] : 0) -
+                          (int64_t)(i < b->length ? b->digits[i] : 0) - borrow;
+        borrow = current < 0;
+        digits[i] = (uint32_t)(current + (borrow << 32));
+    }
+    return iod_bigint_new(a->negative, digits, length);
+}
+
+static iod_bigint iod_bigint_subtract(const iod_bigint *a, const iod_bigint *b) {
+    iod_bigint negated = iod_bigint_new(!b->negative, b->digits, b->length);
+    return iod_bigint_add(a, &negated);
+}
+
+static iod_bigint iod_bigint_multiply(const iod_bigint *a, const iod_bigint *b) {
+    size_t length = a->length + b->length;
+    uint32_t *digits = calloc(length + 1, sizeof(uint32_t));
+    for (size_t i = 0; i < a->length; i++) {
+        uint64_t carry = 0;
+        for (size_t j = 0; j < b->length; j++) {
+            uint64_t current = digits[i + j] + (uint64_t)a->digits[i] * b->digits[j] + carry;
+            digits[i + j] = (uint32_t)current;
+            carry = current >> 32;
+        }
+        digits[i + b->length] = (uint32_t)carry;
+    }
+    return iod_bigint_new(a->negative != b->negative, digits, length);
+}
+
+/* Number of bits of the magnitude, 0 for zero */
+static uint64_t iod_bigint_bits(const iod_bigint *value) {
+    if (value->length == 0) {
+        return 0;
+    }
+    return value->length * 32 - __builtin_clz(value->digits[value->length - 1]);
+}
+
+static void iod_print_bigint(FILE *out, const iod_bigint *value) {
+    if (value->length == 0) {
+        fputc('0', out);
+        return;
+    }
+
+    /* Peel off 9 decimal digits at a time, least significant first */
+    size_t length = value->length;
+    uint32_t *rest = malloc(length * sizeof(uint32_t));
+    memcpy(rest, value->digits, length * sizeof(uint32_t));
+    uint32_t *chunks = malloc((2 * length + 1) * sizeof(uint32_t));
+    size_t count = 0;
+    while (length > 0) {
+        uint64_t remainder = 0;
+        for (size_t i = length; i > 0; i--) {
+            uint64_t current = (remainder << 32) | rest[i - 1];
+            rest[i - 1] = (uint32_t)(current / 1000000000);
+            remainder = current % 1000000000;
+        }
+        chunks[count++] = (uint32_t)remainder;
+        while (length > 0 && rest[length - 1] == 0) {
+            length--;
+        }
+    }
+
+    if (value->negative) {
+        fputc('-', out);
+    }
+    fprintf(out, "%u", (unsigned)chunks[count - 1]);
+    for (size_t i = count - 1; i > 0; i--) {
+        fprintf(out, "%09u", (unsigned)chunks[i - 1]);
+    }
+    free(rest);
+    free(chunks);
+}
+
+/* Goes through the decimal form, `strtod` rounds it correctly */
+static double iod_bigint_to_float(const iod_bigint *value) {
+    char *str;
+    size_t length;
+    FILE *out = open_memstream(&str, &length);
+    iod_print_bigint(out, value);
+    fclose(out);
+    double result = strtod(str, NULL);
+    free(str);
+    return result;
+}
+
+static double iod_to_float(iod_value value) {
+    switch (value.tag) {
+    case IOD_FLOAT:
+        return value.as.flt;
+    case IOD_SIGNED_INT:
+        return (double)value.as.signed_int;
+    case IOD_UNSIGNED_INT:
+        return (double)value.as.unsigned_int;
+    case IOD_BIG_INT:
+        return iod_bigint_to_float(value.as.big_int);
+    default:
+        iod_fail("Expected a number during execution");
+        return 0.0;
+    }
+}
+
+static int iod_to_bool(iod_value value) {
+    switch (value.tag) {
+    case IOD_BOOL:
+        return value.as.boolean;
+    case IOD_UNSIGNED_INT:
+        return value.as.unsigned_int != 0;
+    case IOD_SIGNED_INT:
+        return value.as.signed_int != 0;
+    /* Big integers are never zero */
+    case IOD_BIG_INT:
+        return 1;
+    case IOD_FLOAT:
+        return value.as.flt != 0.0;
+    case IOD_STRING:
+        return value.as.string[0] != '\0';
+    case IOD_LIST:
+        return value.as.list->length != 0;
+    case IOD_QUOTATION:
+        return 1;
+    }
+    return 0;
+}
+
+/* Local variables of every active function call, `iod_frames` holds the
+ * first slot of each call */
+static iod_value iod_locals[IOD_STACK_SIZE];
+static size_t iod_locals_top = 0;
+static size_t iod_frames[IOD_FRAMES_SIZE];
+static size_t iod_fp = 0;
+
+/* Reserves the local variable slots of a function call */
+void iod_frame_enter(uint64_t size) {
+    if (iod_fp == IOD_FRAMES_SIZE || IOD_STACK_SIZE - iod_locals_top < size) {
+        iod_fail("Stack overflow during execution");
+    }
+    iod_frames[iod_fp++] = iod_locals_top;
+    iod_locals_top += size;
+}
+
+void iod_frame_exit(void) { iod_locals_top = iod_frames[--iod_fp]; }
+
+/* Binds the top of the stack to a local variable of the current call */
+void iod_local_set(uint64_t slot) { iod_locals[iod_frames[iod_fp - 1] + slot] = iod_pop(); }
+
+void iod_local_get(uint64_t slot) { iod_push(iod_locals[iod_frames[iod_fp - 1] + slot]); }
+
+/* Global variables, indexed in the order they are declared */
+static iod_value iod_variables[IOD_VARIABLES_SIZE];
+static int iod_variables_set[IOD_VARIABLES_SIZE];
+
+static const char *iod_type_name(iod_value value) {
+    switch (value.tag) {
+    case IOD_STRING:
+        return "string";
+    case IOD_BOOL:
+        return "bool";
+    case IOD_LIST:
+        return "list";
+    case IOD_QUOTATION:
+        return "quotation";
+    default:
+        return "number";
+    }
+}
+
+/* `name @` */
+void iod_variable_fetch(uint64_t index, const char *name) {
+    if (!iod_variables_set[index]) {
+        fflush(stdout);
+        fprintf(stderr, "Variable `%s` was read before anything was stored in it\n", name);
+        exit(1);
+    }
+    iod_push(iod_variables[index]);
+}
+
+/* `name !`, `type` is NULL for variables declared without one */
+void iod_variable_store(uint64_t index, const char *name, const char *type) {
+    iod_value value = iod_pop();
+    if (type != NULL && strcmp(type, iod_type_name(value)) != 0) {
+        fflush(stdout);
+        fprintf(stderr, "Variable `%s` holds %s but got %s\n", name, type, iod_type_name(value));
+        exit(1);
+    }
+    iod_variables[index] = value;
+    iod_variables_set[index] = 1;
+}
+
+/* Pops the condition of an `if` block */
+int iod_pop_condition(void) { return iod_to_bool(iod_pop()); }
+
+/* Formats a float the same way Rust's `Display` for `f64` does: the shortest
+ * representation that round-trips, never in scientific notation */
+static void iod_print_float(FILE *out, double value) {
+    if (isnan(value)) {
+        fputs("NaN", out);
+        return;
+    }
+    if (isinf(value)) {
+        fputs(value < 0 ? "-inf" : "inf", out);
+        return;
+    }
+
+    char buf[32];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(buf, sizeof(buf), "%.*e", precision, value);
+        if (strtod(buf, NULL) == value) {
+            break;
+        }
+    }
+
+    char *cursor = buf;
+    if (*cursor == '-') {
+        fputc('-', out);
+        cursor++;
+    }
+
+    char digits[32];
+    int count = 0;
+    while (*cursor != 'e') {
+        if (*cursor != '.') {
+            digits[count++] = *cursor;
+        }
+        cursor++;
+    }
+    int exponent = atoi(cursor + 1);
+
+    if (exponent < 0) {
+        fputs("0.", out);
+        for (int i = 0; i < -exponent - 1; i++) {
+            fputc('0', out);
+        }
+        fwrite(digits, 1, count, out);
+    } else if (exponent + 1 >= count) {
+        fwrite(digits, 1, count, out);
+        for (int i = 0; i < exponent + 1 - count; i++) {
+            fputc('0', out);
+        }
+    } else {
+        fwrite(digits, 1, exponent + 1, out);
+        fputc('.', out);
+        fwrite(digits + exponent + 1, 1, count - exponent - 1, out);
+    }
+}
+
+/* Prints a string the same way Rust's `Debug` for `str` does */
+static void iod_print_quoted(FILE *out, const char *str) {
+    fputc('"', out);
+    for (const unsigned char *c = (const unsigned char *)str; *c != '\0'; c++) {
+        switch (*c) {
+        case '"':
+            fputs("\\\"", out);
+            break;
+        case '\\':
+            fputs("\\\\", out);
+            break;
+        case '\n':
+            fputs("\\n", out);
+            break;
+        case '\r':
+            fputs("\\r", out);
+            break;
+        case '\t':
+            fputs("\\t", out);
+            break;
+        default:
+            if (*c < 0x20 || *c == 0x7f) {
+                fprintf(out, "\\u{%x}", *c);
+            } else {
+                fputc(*c, out);
+            }
+        }
+    }
+    fputc('"', out);
+}
+
+static void iod_print_value(FILE *out, iod_value value) {
+    switch (value.tag) {
+    case IOD_STRING:
+        fputs(value.as.string, out);
+        break;
+    case IOD_UNSIGNED_INT:
+        fprintf(out, "%llu", (unsigned long long)value.as.unsigned_int);
+        break;
+    case IOD_SIGNED_INT:
+        fprintf(out, "%lld", (long long)value.as.signed_int);
+        break;
+    case IOD_BIG_INT:
+        iod_print_bigint(out, value.as.big_int);
+        break;
+    case IOD_FLOAT:
+        iod_print_float(out, value.as.flt);
+        break;
+    case IOD_BOOL:
+        fputs(value.as.boolean ? "true" : "false", out);
+        break;
+    case IOD_LIST:
+        fputc('{', out);
+        for (size_t i = 0; i < value.as.list->length; i++) {
+            fputc(' ', out);
+            iod_value item = value.as.list->items[i];
+            if (item.tag == IOD_STRING) {
+                iod_print_quoted(out, item.as.string);
+            } else {
+                iod_print_value(out, item);
+            }
+        }
+        fputs(" }", out);
+        break;
+    case IOD_QUOTATION:
+        fputs("[ quotation ]", out);
+        break;
+    }
+}
+
+/* Reads a line from stdin with surrounding whitespace removed, the result
+ * is never freed as strings live until the program exits */
+static char *iod_read_line(void) {
+    size_t capacity = 64;
+    size_t length = 0;
+    char *line = malloc(capacity);
+    int c;
+
+    while ((c = getchar()) != EOF) {
+        if (length + 1 == capacity) {
+            capacity *= 2;
+            line = realloc(line, capacity);
+        }
+        line[length++] = (char)c;
+        if (c == '\n') {
+            break;
+        }
+    }
+    if (c == EOF && length == 0) {
+        iod_fail("Reached the end of the input");
+    }
+    line[length] = '\0';
+
+    while (length > 0 && (line[length - 1] == ' ' || line[length - 1] == '\t' ||
+                          line[length - 1] == '\n' || line[length - 1] == '\r')) {
+        line[--length] = '\0';
+    }
+    char *start = line;
+    while (*start == ' ' || *start == '\t') {
+        start++;
+    }
+    return start;
+}
+
+void iod_word_drop(void) {
+    if (iod_sp > 0) {
+        iod_sp--;
+    }
+}
+
+void iod_word_dup(void) {
+    iod_value value = iod_pop();
+    iod_push(value);
+    iod_push(value);
+}
+
+void iod_word_swap(void) {
+    iod_value a = iod_pop();
+    iod_value b = iod_pop();
+    iod_push(a);
+    iod_push(b);
+}
+
+
+void iod_word_print(void) {
+    iod_print_value(stdout, iod_pop());
+    fputc('\n', stdout);
+}
+
+void iod_word_get_line(void) { iod_push_string(iod_read_line()); }
+
+static void iod_malformed_input(const char *expected, const char *line) {
+    fflush(stdout);
+    fprintf(stderr, "Expected %s but got `%s`\n", expected, line);
+    exit(1);
+}
+
+void iod_word_get_int(void) {
+    char *line = iod_read_line();
+    char *end;
+    errno = 0;
+    long long value = strtoll(line, &end, 10);
+    if (*line == '\0' || *end != '\0' || errno == ERANGE) {
+        iod_malformed_input("a signed integer", line);
+    }
+    iod_push_signed_int(value);
+}
+
+void iod_word_get_uint(void) {
+    char *line = iod_read_line();
+    char *end;
+    errno = 0;
+    unsigned long long value = strtoull(line, &end, 10);
+    if (*line == '\0' || *line == '-' || *end != '\0' || errno == ERANGE) {
+        iod_malformed_input("an unsigned integer", line);
+    }
+    iod_push_unsigned_int(value);
+}
+
+void iod_word_get_float(void) {
+    char *line = iod_read_line();
+    char *end;
+    double value = strtod(line, &end);
+    if (*line == '\0' || *end != '\0') {
+        iod_malformed_input("a floating point number", line);
+    }
+    iod_push_float(value);
+}
+
+void iod_word_read_all(void) {
+    size_t capacity = 64;
+    size_t length = 0;
+    char *contents = malloc(capacity);
+    int c;
+
+    while ((c = getchar()) != EOF) {
+        if (length + 1 == capacity) {
+            capacity *= 2;
+            contents = realloc(contents, capacity);
+        }
+        contents[length++] = (char)c;
+    }
+    contents[length] = '\0';
+    iod_push_string(contents);
+}
+
+void iod_word_eof(void) {
+    int c = getchar();
+    if (c != EOF) {
+        ungetc(c, stdin);
+    }
+    iod_push_bool(c == EOF);
+}
+
+/* Integer that fits in 64 bits */
+static int iod_is_small_integer(iod_value value) {
+    return value.tag == IOD_UNSIGNED_INT || value.tag == IOD_SIGNED_INT;
+}
+
+static int iod_is_integer(iod_value value) {
+    return iod_is_small_integer(value) || value.tag == IOD_BIG_INT;
+}
+
+static __int128 iod_to_int128(iod_value value) {
+    return value.tag == IOD_UNSIGNED_INT ? (__int128)value.as.unsigned_int
+                                         : (__int128)value.as.signed_int;
+}
+
+/* Any integer as a big integer, the digits of a small one are written to
+ * `buffer` */
+static iod_bigint iod_to_bigint(iod_value value, uint32_t *buffer) {
+    if (value.tag == IOD_BIG_INT) {
+        return *value.as.big_int;
+    }
+    return iod_bigint_from_int128(iod_to_int128(value), buffer);
+}
+
+/* Pushes an integer that doesn't fit in 64 bits, taking its digits */
+static void iod_push_big(iod_bigint value) {
+    iod_bigint *big_int = malloc(sizeof(iod_bigint));
+    *big_int = value;
+    iod_value v = {.tag = IOD_BIG_INT, .as.big_int = big_int};
+    iod_push(v);
+}
+
+/* Pushes the smallest integer type holding `value` */
+static void iod_push_int128(__int128 value) {
+    if (value >= 0 && value <= (__int128)UINT64_MAX) {
+        iod_push_unsigned_int((uint64_t)value);
+    } else if (value >= INT64_MIN && value <= INT64_MAX) {
+        iod_push_signed_int((int64_t)value);
+    } else {
+        iod_push_big(iod_bigint_from_int128(value, malloc(4 * sizeof(uint32_t))));
+    }
+}
+
+/* Same as `iod_push_int128`, takes the digits of `value` */
+static void iod_push_bigint(iod_bigint value) {
+    __int128 small;
+    if (iod_bigint_to_int128(&value, &small) && small >= INT64_MIN &&
+        small <= (__int128)UINT64_MAX) {
+        free(value.digits);
+        iod_push_int128(small);
+    } else {
+        iod_push_big(value);
+    }
+}
+
+/* Pushes an integer literal that doesn't fit in 64 bits, given in decimal
+ * with an optional leading `-` */
+void iod_push_big_int(const char *literal) {
+    int negative = *literal == '-';
+    const char *digit = literal + negative;
+    uint32_t *digits = malloc((strlen(digit) / 9 + 2) * sizeof(uint32_t));
+    size_t length = 0;
+    for (; *digit != '\0'; digit++) {
+        uint64_t carry = (uint64_t)(*digit - '0');
+        for (size_t i = 0; i < length; i++) {
+            uint64_t current = (uint64_t)digits[i] * 10 + carry;
+            digits[i] = (uint32_t)current;
+            carry = current >> 32;
+        }
+        if (carry > 0) {
+            digits[length++] = (uint32_t)carry;
+        }
+    }
+    iod_push_bigint(iod_bigint_new(negative, digits, length));
+}
+
+/* Integers are added, subtracted and multiplied exactly, promoted to big
+ * integers when the result doesn't fit in 128 bits. Anything else as floats */
+#define IOD_ARITHMETIC(name, checked, big, op)                                                    \
+    void name(void) {                                                                             \
+        iod_value x = iod_pop();                                                                  \
+        iod_value y = iod_pop();                                                                  \
+        __int128 result;                                                                          \
+        if (iod_is_small_integer(x) && iod_is_small_integer(y) &&                                 \
+            !checked(iod_to_int128(y), iod_to_int128(x), &result)) {                              \
+            iod_push_int128(result);                                                              \
+            return;                                                                               \
+        }                                                                                         \
+        if (iod_is_integer(x) && iod_is_integer(y)) {                                             \
+            uint32_t a_buffer[4], b_buffer[4];                                                    \
+            iod_bigint a = iod_to_bigint(x, a_buffer);                                            \
+            iod_bigint b = iod_to_bigint(y, b_buffer);                                            \
+            iod_push_bigint(big(&b, &a));                                                         \
+            return;                                                                               \
+        }                                                                                         \
+        double a = iod_to_float(x);                                                               \
+        double b = iod_to_float(y);                                                               \
+        iod_push_float(op);                                                                       \
+    }
+
+IOD_ARITHMETIC(iod_word_add, __builtin_add_overflow, iod_bigint_add, a + b)
+IOD_ARITHMETIC(iod_word_subtract, __builtin_sub_overflow, iod_bigint_subtract, b - a)
+IOD_ARITHMETIC(iod_word_multiply, __builtin_mul_overflow, iod_bigint_multiply, b * a)
+
+void iod_word_divide(void) {
+    double a = iod_to_float(iod_pop());
+    double b = iod_to_float(iod_pop());
+    iod_push_float(b / a);
+}
+
+/* Returned by comparisons when either side is NaN */
+#define IOD_UNORDERED 2
+
+/* Exact comparison of an integer with a float */
+static int iod_compare_int_float(iod_value x, double y) {
+    if (isnan(y)) {
+        return IOD_UNORDERED;
+    }
+    if (isinf(y)) {
+        return y > 0 ? -1 : 1;
+    }
+    double truncated = trunc(y);
+    int ordering;
+    if (iod_is_small_integer(x) && fabs(truncated) < 0x1p127) {
+        __int128 integer = (__int128)truncated;
+        ordering = (iod_to_int128(x) > integer) - (iod_to_int128(x) < integer);
+    } else {
+        uint32_t buffer[4];
+        iod_bigint integer = iod_bigint_from_integral(truncated);
+        iod_bigint big_x = iod_to_bigint(x, buffer);
+        ordering = iod_bigint_compare(&big_x, &integer);
+        free(integer.digits);
+    }
+    if (ordering != 0) {
+        return ordering;
+    }
+    /* On a tie the fractional part decides */
+    return (truncated > y) - (truncated < y);
+}
+
+/* Numeric comparison, exact between integers and between an integer and a
+ * float */
+static int iod_compare_numbers(iod_value a, iod_value b) {
+    if (iod_is_small_integer(a) && iod_is_small_integer(b)) {
+        __int128 x = iod_to_int128(a);
+        __int128 y = iod_to_int128(b);
+        return (x > y) - (x < y);
+    }
+    if (iod_is_integer(a) && iod_is_integer(b)) {
+        uint32_t a_buffer[4], b_buffer[4];
+        iod_bigint x = iod_to_bigint(a, a_buffer);
+        iod_bigint y = iod_to_bigint(b, b_buffer);
+        return iod_bigint_compare(&x, &y);
+    }
+    if (iod_is_integer(a)) {
+        return iod_compare_int_float(a, b.as.flt);
+    }
+    if (iod_is_integer(b)) {
+        int ordering = iod_compare_int_float(b, a.as.flt);
+        return ordering == IOD_UNORDERED ? ordering : -ordering;
+    }
+    double x = a.as.flt;
+    double y = b.as.flt;
+    if (isnan(x) || isnan(y)) {
+        return IOD_UNORDERED;
+    }
+    return (x > y) - (x < y);
+}
+
+static int iod_is_number(iod_value value) {
+    return iod_is_integer(value) || value.tag == IOD_FLOAT;
+}
+
+/* Same as `compare_values`: numbers by value, strings lexicographically,
+ * false before true and lists item by item */
+static int iod_compare_values(const char *word, iod_value a, iod_value b) {
+    if (iod_is_number(a) && iod_is_number(b)) {
+        return iod_compare_numbers(a, b);
+    }
+    if (a.tag == IOD_STRING && b.tag == IOD_STRING) {
+        int ordering = strcmp(a.as.string, b.as.string);
+        return (ordering > 0) - (ordering < 0);
+    }
+    if (a.tag == IOD_BOOL && b.tag == IOD_BOOL) {
+        return a.as.boolean - b.as.boolean;
+    }
+    if (a.tag == IOD_LIST && b.tag == IOD_LIST) {
+        iod_list *x = a.as.list;
+        iod_list *y = b.as.list;
+        for (size_t i = 0; i < x->length && i < y->length; i++) {
+            int ordering = iod_compare_values(word, x->items[i], y->items[i]);
+            if (ordering != 0) {
+                return ordering;
+            }
+        }
+        return (x->length > y->length) - (x->length < y->length);
+    }
+    fflush(stdout);
+    fprintf(stderr, "`%s` can't compare %s and %s\n", word, iod_type_name(a), iod_type_name(b));
+    exit(1);
+}
+
+/* Equality of the locals two quotations captured */
+static int iod_values_equal(iod_value a, iod_value b) {
+    if (a.tag == IOD_LIST || b.tag == IOD_LIST) {
+        if (a.tag != b.tag || a.as.list->length != b.as.list->length) {
+            return 0;
+        }
+        for (size_t i = 0; i < a.as.list->length; i++) {
+            if (!iod_values_equal(a.as.list->items[i], b.as.list->items[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+    if (a.tag == IOD_QUOTATION || b.tag == IOD_QUOTATION) {
+        if (a.tag != b.tag || a.as.quotation->code != b.as.quotation->code ||
+            a.as.quotation->count != b.as.quotation->count) {
+            return 0;
+        }
+        for (size_t i = 0; i < a.as.quotation->count; i++) {
+            if (!iod_values_equal(a.as.quotation->locals[i], b.as.quotation->locals[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+    if (a.tag == IOD_STRING || b.tag == IOD_STRING) {
+        return a.tag == b.tag && strcmp(a.as.string, b.as.string) == 0;
+    }
+    if (a.tag == IOD_BOOL && b.tag == IOD_BOOL) {
+        return a.as.boolean == b.as.boolean;
+    }
+    if (a.tag == IOD_BOOL) {
+        return iod_to_float(b) == (double)a.as.boolean;
+    }
+    if (b.tag == IOD_BOOL) {
+        return iod_to_float(a) == (double)b.as.boolean;
+    }
+    return iod_compare_numbers(a, b) == 0;
+}
+
+static int iod_equal_values(const char *word, iod_value a, iod_value b) {
+    if (a.tag == IOD_LIST && b.tag == IOD_LIST) {
+        if (a.as.list->length != b.as.list->length) {
+            return 0;
+        }
+        for (size_t i = 0; i < a.as.list->length; i++) {
+            if (!iod_equal_values(word, a.as.list->items[i], b.as.list->items[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+    if (a.tag == IOD_QUOTATION && b.tag == IOD_QUOTATION) {
+        return iod_values_equal(a, b);
+    }
+    return iod_compare_values(word, a, b) == 0;
+}
+
+#define IOD_COMPARISON(name, word, test)                                                          \
+    void name(void) {                                                                             \
+        iod_value x = iod_pop();                                                                  \
+        iod_value y = iod_pop();                                                                  \
+        int ordering = iod_compare_values(word, y, x);                                            \
+        iod_push_bool(ordering != IOD_UNORDERED && (test));                                       \
+    }
+
+IOD_COMPARISON(iod_word_less, "<", ordering < 0)
+IOD_COMPARISON(iod_word_more, ">", ordering > 0)
+IOD_COMPARISON(iod_word_less_or_equal, "<=", ordering <= 0)
+IOD_COMPARISON(iod_word_more_or_equal, ">=", ordering >= 0)
+
+void iod_word_equal(void) {
+    iod_value x = iod_pop();
+    iod_value y = iod_pop();
+    iod_push_bool(iod_equal_values("==", y, x));
+}
+
+void iod_word_not_equal(void) {
+    iod_value x = iod_pop();
+    iod_value y = iod_pop();
+    iod_push_bool(!iod_equal_values("!=", y, x));
+}
+
+void iod_word_assert(void) {
+    if (!iod_to_bool(iod_pop())) {
+        iod_fail("Assertion failed");
+    }
+}
+
+void iod_word_assert_eq(void) {
+    iod_value expected = iod_pop();
+    iod_value actual = iod_pop();
+    if (!iod_equal_values("assert_eq", actual, expected)) {
+        iod_fail("Assertion failed");
+    }
+}
+
+/* Stack positions of every `{` that is not closed yet */
+static size_t iod_list_starts[IOD_FRAMES_SIZE];
+static size_t iod_list_top = 0;
+
+void iod_list_start(void) {
+    if (iod_list_top == IOD_FRAMES_SIZE) {
+        iod_fail("Stack overflow during execution");
+    }
+    iod_list_starts[iod_list_top++] = iod_sp;
+}
+
+/* Collects everything pushed since the matching `{` into a list */
+void iod_list_end(void) {
+    size_t start = iod_list_starts[--iod_list_top];
+    if (start > iod_sp) {
+        start = iod_sp;
+    }
+    iod_list *list = malloc(sizeof(iod_list));
+    list->length = iod_sp - start;
+    list->items = malloc(list->length * sizeof(iod_value) + 1);
+    memcpy(list->items, &iod_stack[start], list->length * sizeof(iod_value));
+    iod_sp = start;
+    iod_value v = {.tag = IOD_LIST, .as.list = list};
+    iod_push(v);
+}
+
+static void iod_push_list(iod_value *items, size_t length) {
+    iod_list *list = malloc(sizeof(iod_list));
+    list->length = length;
+    list->items = items;
+    iod_value v = {.tag = IOD_LIST, .as.list = list};
+    iod_push(v);
+}
+
+/* Pushes a quotation, capturing the locals of the current call */
+void iod_push_quotation(void (*code)(void)) {
+    iod_quotation *quotation = malloc(sizeof(iod_quotation));
+    quotation->code = code;
+    quotation->count = iod_fp > 0 ? iod_locals_top - iod_frames[iod_fp - 1] : 0;
+    quotation->locals = malloc(quotation->count * sizeof(iod_value) + 1);
+    if (quotation->count > 0) {
+        memcpy(quotation->locals, &iod_locals[iod_frames[iod_fp - 1]],
+               quotation->count * sizeof(iod_value));
+    }
+    iod_value v = {.tag = IOD_QUOTATION, .as.quotation = quotation};
+    iod_push(v);
+}
+
+static void iod_type_error(const char *word, const char *expected, iod_value actual) {
+    fflush(stdout);
+    fprintf(stderr, "`%s` expects %s but got %s\n", word, expected, iod_type_name(actual));
+    exit(1);
+}
+
+static iod_list *iod_pop_list(const char *word) {
+    iod_value value = iod_pop();
+    if (value.tag != IOD_LIST) {
+        iod_type_error(word, "list", value);
+    }
+    return value.as.list;
+}
+
+static iod_quotation *iod_pop_quotation(const char *word) {
+    iod_value value = iod_pop();
+    if (value.tag != IOD_QUOTATION) {
+        iod_type_error(word, "quotation", value);
+    }
+    return value.as.quotation;
+}
+
+/* Runs a quotation in its own frame, starting with the captured locals */
+static void iod_call_quotation(iod_quotation *quotation) {
+    iod_frame_enter(quotation->count);
+    if (quotation->count > 0) {
+        memcpy(&iod_locals[iod_frames[iod_fp - 1]], quotation->locals,
+               quotation->count * sizeof(iod_value));
+    }
+    quotation->code();
+    iod_frame_exit();
+}
+
+void iod_word_call(void) { iod_call_quotation(iod_pop_quotation("call")); }
+
+void iod_word_map(void) {
+    iod_quotation *quotation = iod_pop_quotation("map");
+    iod_list *list = iod_pop_list("map");
+    iod_value *items = malloc(list->length * sizeof(iod_value) + 1);
+    for (size_t i = 0; i < list->length; i++) {
+        iod_push(list->items[i]);
+        iod_call_quotation(quotation);
+        items[i] = iod_pop();
+    }
+    iod_push_list(items, list->length);
+}
+
+void iod_word_filter(void) {
+    iod_quotation *quotation = iod_pop_quotation("filter");
+    iod_list *list = iod_pop_list("filter");
+    iod_value *items = malloc(list->length * sizeof(iod_value) + 1);
+    size_t length = 0;
+    for (size_t i = 0; i < list->length; i++) {
+        iod_push(list->items[i]);
+        iod_call_quotation(quotation);
+        if (iod_to_bool(iod_pop())) {
+            items[length++] = list->items[i];
+        }
+    }
+    iod_push_list(items, length);
+}
+
+void iod_word_fold(void) {
+    iod_quotation *quotation = iod_pop_quotation("fold");
+    iod_value initial = iod_pop();
+    iod_list *list = iod_pop_list("fold");
+    iod_push(initial);
+    for (size_t i = 0; i < list->length; i++) {
+        iod_push(list->items[i]);
+        iod_call_quotation(quotation);
+    }
+}
+
+void iod_word_each(void) {
+    iod_quotation *quotation = iod_pop_quotation("each");
+    iod_list *list = iod_pop_list("each");
+    for (size_t i = 0; i < list->length; i++) {
+        iod_push(list->items[i]);
+        iod_call_quotation(quotation);
+    }
+}
+
+void iod_word_times(void) {
+    iod_quotation *quotation = iod_pop_quotation("times");
+    double count = iod_to_float(iod_pop());
+    for (uint64_t i = 0; i < (count > 0 ? (uint64_t)count : 0); i++) {
+        iod_call_quotation(quotation);
+    }
+}
+
+static const char *iod_pop_string(const char *word) {
+    iod_value value = iod_pop();
+    if (value.tag != IOD_STRING) {
+        iod_type_error(word, "string", value);
+    }
+    return value.as.string;
+}
+
+void iod_word_read_file(void) {
+    const char *path = iod_pop_string("read_file");
+    FILE *file = fopen(path, "rb");
+    if (file == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "Could not read `%s`: %s\n", path, strerror(errno));
+        exit(1);
+    }
+    size_t capacity = 64;
+    size_t length = 0;
+    char *contents = malloc(capacity);
+    size_t read;
+    while ((read = fread(contents + length, 1, capacity - length - 1, file)) > 0) {
+        length += read;
+        if (length + 1 == capacity) {
+            capacity *= 2;
+            contents = realloc(contents, capacity);
+        }
+    }
+    fclose(file);
+    contents[length] = '\0';
+    iod_push_string(contents);
+}
+
+void iod_word_write_file(void) {
+    const char *path = iod_pop_string("write_file");
+    iod_value value = iod_pop();
+    FILE *file = fopen(path, "wb");
+    if (file == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "Could not write `%s`: %s\n", path, strerror(errno));
+        exit(1);
+    }
+    iod_print_value(file, value);
+    fclose(file);
+}
+
+void iod_word_env(void) {
+    const char *value = getenv(iod_pop_string("env"));
+    iod_push_string(value != NULL ? value : "");
+}
+
+void iod_word_now(void) {
+    struct timespec now;
+    timespec_get(&now, TIME_UTC);
+    iod_push_float((double)now.tv_sec + (double)now.tv_nsec / 1e9);
+}
+
+/* xorshift64*, seeded from the clock on first use */
+static uint64_t iod_random_state = 0;
+
+void iod_word_random(void) {
+    if (iod_random_state == 0) {
+        struct timespec now;
+        timespec_get(&now, TIME_UTC);
+        iod_random_state = ((uint64_t)now.tv_sec * 1000000007ULL) ^ (uint64_t)now.tv_nsec;
+        iod_random_state |= 1;
+    }
+    iod_random_state ^= iod_random_state >> 12;
+    iod_random_state ^= iod_random_state << 25;
+    iod_random_state ^= iod_random_state >> 27;
+    uint64_t bits = iod_random_state * 2685821657736338717ULL;
+    iod_push_float((double)(bits >> 11) / (double)(1ULL << 53));
+}
+
+static void iod_conversion_error(const char *word, iod_value value, const char *target) {
+    fflush(stdout);
+    fprintf(stderr, "`%s` can't convert ", word);
+    if (value.tag == IOD_STRING) {
+        iod_print_quoted(stderr, value.as.string);
+    } else {
+        iod_print_value(stderr, value);
+    }
+    fprintf(stderr, " to %s\n", target);
+    exit(1);
+}
+
+/* Truncates toward zero, fails unless the result is in `min..max` */
+static double iod_truncate_float(const char *word, iod_value value, double min, double max,
+                                 const char *target) {
+    double truncated = trunc(value.as.flt);
+    if (!(truncated >= min && truncated < max)) {
+        iod_conversion_error(word, value, target);
+    }
+    return truncated;
+}
+
+void iod_word_to_int(void) {
+    iod_value value = iod_pop();
+    const char *target = "a signed integer";
+    switch (value.tag) {
+    case IOD_UNSIGNED_INT:
+        if (value.as.unsigned_int > INT64_MAX) {
+            iod_conversion_error("to_int", value, target);
+        }
+        iod_push_signed_int((int64_t)value.as.unsigned_int);
+        break;
+    case IOD_SIGNED_INT:
+        iod_push(value);
+        break;
+    /* Big integers never fit in 64 bits */
+    case IOD_BIG_INT:
+        iod_conversion_error("to_int", value, target);
+        break;
+    case IOD_FLOAT:
+        iod_push_signed_int(
+            (int64_t)iod_truncate_float("to_int", value, -0x1p63, 0x1p63, target));
+        break;
+    case IOD_BOOL:
+        iod_push_signed_int(value.as.boolean);
+        break;
+    case IOD_STRING: {
+        /* Same syntax as Rust's `parse`, so no leading whitespace */
+        const char *str = value.as.string;
+        char *end;
+        errno = 0;
+        long long result = strtoll(str, &end, 10);
+        if (!(*str == '-' || *str == '+' || (*str >= '0' && *str <= '9')) || *end != '\0' ||
+            errno == ERANGE) {
+            iod_conversion_error("to_int", value, target);
+        }
+        iod_push_signed_int(result);
+        break;
+    }
+    default:
+        iod_type_error("to_int", "number, string or bool", value);
+    }
+}
+
+void iod_word_to_uint(void) {
+    iod_value value = iod_pop();
+    const char *target = "an unsigned integer";
+    switch (value.tag) {
+    case IOD_UNSIGNED_INT:
+        iod_push(value);
+        break;
+    case IOD_SIGNED_INT:
+        if (value.as.signed_int < 0) {
+            iod_conversion_error("to_uint", value, target);
+        }
+        iod_push_unsigned_int((uint64_t)value.as.signed_int);
+        break;
+    case IOD_BIG_INT:
+        iod_conversion_error("to_uint", value, target);
+        break;
+    case IOD_FLOAT:
+        iod_push_unsigned_int(
+            (uint64_t)iod_truncate_float("to_uint", value, 0.0, 0x1p64, target));
+        break;
+    case IOD_BOOL:
+        iod_push_unsigned_int(value.as.boolean);
+        break;
+    case IOD_STRING: {
+        const char *str = value.as.string;
+        char *end;
+        errno = 0;
+        unsigned long long result = strtoull(str, &end, 10);
+        if (!(*str == '+' || (*str >= '0' && *str <= '9')) || *end != '\0' || errno == ERANGE) {
+            iod_conversion_error("to_uint", value, target);
+        }
+        iod_push_unsigned_int(result);
+        break;
+    }
+    default:
+        iod_type_error("to_uint", "number, string or bool", value);
+    }
+}
+
+void iod_word_to_float(void) {
+    iod_value value = iod_pop();
+    switch (value.tag) {
+    case IOD_UNSIGNED_INT:
+    case IOD_SIGNED_INT:
+    case IOD_BIG_INT:
+    case IOD_FLOAT:
+        iod_push_float(iod_to_float(value));
+        break;
+    case IOD_BOOL:
+        iod_push_float(value.as.boolean);
+        break;
+    case IOD_STRING: {
+        /* `strtod` also takes hex floats and `nan(...)`, Rust's `parse`
+         * doesn't */
+        const char *str = value.as.string;
+        char *end;
+        double result = strtod(str, &end);
+        if (*str == '\0' || *str == ' ' || *str == '\t' || *str == '\n' ||
+            strpbrk(str, "xX(") != NULL || *end != '\0') {
+            iod_conversion_error("to_float", value, "a floating point number");
+        }
+        iod_push_float(result);
+        break;
+    }
+    default:
+        iod_type_error("to_float", "number, string or bool", value);
+    }
+}
+
+void iod_word_to_string(void) {
+    iod_value value = iod_pop();
+    if (value.tag == IOD_STRING) {
+        iod_push(value);
+        return;
+    }
+    char *str;
+    size_t length;
+    FILE *out = open_memstream(&str, &length);
+    iod_print_value(out, value);
+    fclose(out);
+    iod_push_string(str);
+}
+
+void iod_word_to_bool(void) { iod_push_bool(iod_to_bool(iod_pop())); }
+
+/* Operand of the bitwise and integer division words */
+static __int128 iod_integer_operand(const char *word, iod_value value) {
+    if (value.tag == IOD_BIG_INT) {
+        fflush(stdout);
+        fprintf(stderr, "`%s` only works on integers that fit in 64 bits\n", word);
+        exit(1);
+    }
+    if (!iod_is_integer(value)) {
+        iod_type_error(word, "integers", value);
+    }
+    return iod_to_int128(value);
+}
+
+static iod_value iod_pop_number(const char *word) {
+    iod_value value = iod_pop();
+    if (!iod_is_integer(value) && value.tag != IOD_FLOAT) {
+        iod_type_error(word, "number", value);
+    }
+    return value;
+}
+
+void iod_word_exit(void) {
+    iod_value code = iod_pop_number("exit");
+    if (!iod_is_small_integer(code) || iod_to_int128(code) < INT32_MIN ||
+        iod_to_int128(code) > INT32_MAX) {
+        iod_conversion_error("exit", code, "a 32 bit exit code");
+    }
+    fflush(stdout);
+    exit((int)iod_to_int128(code));
+}
+
+/* Logical on two bools, bitwise on two integers */
+#define IOD_LOGICAL_OR_BITWISE(name, word, op)                                                    \
+    void name(void) {                                                                             \
+        iod_value x = iod_pop();                                                                  \
+        iod_value y = iod_pop();                                                                  \
+        if (x.tag == IOD_BOOL && y.tag == IOD_BOOL) {                                             \
+            int a = x.as.boolean;                                                                 \
+            int b = y.as.boolean;                                                                 \
+            iod_push_bool(op);                                                                    \
+            return;                                                                               \
+        }                                                                                         \
+        if (y.tag == IOD_BOOL) {                                                                  \
+            iod_type_error(word, "bool", x);                                                      \
+        }                                                                                         \
+        if (x.tag == IOD_BOOL) {                                                                  \
+            iod_type_error(word, "bool", y);                                                      \
+        }                                                                                         \
+        __int128 b = iod_integer_operand(word, y);                                                \
+        __int128 a = iod_integer_operand(word, x);                                                \
+        iod_push_int128(op);                                                                      \
+    }
+
+IOD_LOGICAL_OR_BITWISE(iod_word_and, "and", b & a)
+IOD_LOGICAL_OR_BITWISE(iod_word_or, "or", b | a)
+IOD_LOGICAL_OR_BITWISE(iod_word_xor, "xor", b ^ a)
+
+void iod_word_not(void) {
+    iod_value value = iod_pop();
+    if (value.tag == IOD_BOOL) {
+        iod_push_bool(!value.as.boolean);
+        return;
+    }
+    iod_push_int128(~iod_integer_operand("not", value));
+}
+
+static int iod_pop_shift_count(const char *word) {
+    __int128 count = iod_integer_operand(word, iod_pop());
+    if (count < 0 || count > 63) {
+        fflush(stdout);
+        fprintf(stderr, "`%s` can't shift by %lld, the count must be between 0 and 63\n", word,
+                (long long)count);
+        exit(1);
+    }
+    return (int)count;
+}
+
+void iod_word_shift_left(void) {
+    int count = iod_pop_shift_count("shl");
+    __int128 value = iod_integer_operand("shl", iod_pop());
+    iod_push_int128(value * ((__int128)1 << count));
+}
+
+void iod_word_shift_right(void) {
+    int count = iod_pop_shift_count("shr");
+    __int128 value = iod_integer_operand("shr", iod_pop());
+    iod_push_int128(value >> count);
+}
+
+/* Pops the operands of `mod` and `divmod`, returns 0 if either is a float */
+static int iod_pop_division(const char *word, iod_value *x, iod_value *y) {
+    *x = iod_pop_number(word);
+    *y = iod_pop_number(word);
+    if (x->tag == IOD_FLOAT || y->tag == IOD_FLOAT) {
+        return 0;
+    }
+    if (iod_integer_operand(word, *x) == 0) {
+        fflush(stdout);
+        fprintf(stderr, "`%s` divided by zero\n", word);
+        exit(1);
+    }
+    iod_integer_operand(word, *y);
+    return 1;
+}
+
+void iod_word_mod(void) {
+    iod_value x, y;
+    if (iod_pop_division("mod", &x, &y)) {
+        iod_push_int128(iod_to_int128(y) % iod_to_int128(x));
+    } else {
+        iod_push_float(fmod(iod_to_float(y), iod_to_float(x)));
+    }
+}
+
+void iod_word_divmod(void) {
+    iod_value x, y;
+    if (iod_pop_division("divmod", &x, &y)) {
+        iod_push_int128(iod_to_int128(y) / iod_to_int128(x));
+        iod_push_int128(iod_to_int128(y) % iod_to_int128(x));
+    } else {
+        double a = iod_to_float(x);
+        double b = iod_to_float(y);
+        iod_push_float(trunc(b / a));
+        iod_push_float(fmod(b, a));
+    }
+}
+
+void iod_word_abs(void) {
+    iod_value value = iod_pop_number("abs");
+    if (value.tag == IOD_FLOAT) {
+        iod_push_float(fabs(value.as.flt));
+        return;
+    }
+    if (value.tag == IOD_BIG_INT) {
+        iod_bigint big_int = *value.as.big_int;
+        iod_bigint zero = iod_bigint_new(0, NULL, 0);
+        iod_push_bigint(big_int.negative ? iod_bigint_subtract(&zero, &big_int)
+                                         : iod_bigint_add(&zero, &big_int));
+        return;
+    }
+    __int128 int_value = iod_to_int128(value);
+    iod_push_int128(int_value < 0 ? -int_value : int_value);
+}
+
+void iod_word_neg(void) {
+    iod_value value = iod_pop_number("neg");
+    if (value.tag == IOD_FLOAT) {
+        iod_push_float(-value.as.flt);
+        return;
+    }
+    if (value.tag == IOD_BIG_INT) {
+        iod_bigint zero = iod_bigint_new(0, NULL, 0);
+        iod_push_bigint(iod_bigint_subtract(&zero, value.as.big_int));
+        return;
+    }
+    iod_push_int128(-iod_to_int128(value));
+}
+
+void iod_word_min(void) {
+    iod_value a = iod_pop_number("min");
+    iod_value b = iod_pop_number("min");
+    iod_push(iod_compare_numbers(a, b) < 0 ? a : b);
+}
+
+void iod_word_max(void) {
+    iod_value a = iod_pop_number("max");
+    iod_value b = iod_pop_number("max");
+    iod_push(iod_compare_numbers(a, b) == 1 ? a : b);
+}
+
+/* Most digits an exact `pow` may produce, same limit as the interpreter */
+#define IOD_MAX_POW_DIGITS 100000
+
+/* Integers raised to a non-negative integer stay exact, anything else is
+ * computed with floats */
+void iod_word_pow(void) {
+    iod_value exponent = iod_pop_number("pow");
+    iod_value base = iod_pop_number("pow");
+    if (!iod_is_integer(base) || !iod_is_small_integer(exponent) || iod_to_int128(exponent) < 0) {
+        iod_push_float(pow(iod_to_float(base), iod_to_float(exponent)));
+        return;
+    }
+
+    uint32_t buffer[4];
+    iod_bigint factor = iod_to_bigint(base, buffer);
+    uint64_t remaining = (uint64_t)iod_to_int128(exponent);
+    /* The result has at least `(bits - 1) * exponent` bits, so this never
+     * rejects a result that would fit */
+    uint64_t bits = iod_bigint_bits(&factor);
+    double digits = (bits > 0 ? bits - 1 : 0) * (double)remaining * log10(2.0);
+    if (digits >= IOD_MAX_POW_DIGITS + 1) {
+        fflush(stdout);
+        fprintf(stderr, "`pow` result would have more than %d digits\n", IOD_MAX_POW_DIGITS);
+        exit(1);
+    }
+
+    /* Square and multiply, `factor` only owns its digits once squared */
+    uint32_t *one = malloc(sizeof(uint32_t));
+    *one = 1;
+    iod_bigint result = iod_bigint_new(0, one, 1);
+    int owned = 0;
+    while (remaining > 0) {
+        if (remaining & 1) {
+            iod_bigint product = iod_bigint_multiply(&result, &factor);
+            free(result.digits);
+            result = product;
+        }
+        remaining >>= 1;
+        if (remaining > 0) {
+            iod_bigint square = iod_bigint_multiply(&factor, &factor);
+            if (owned) {
+                free(factor.digits);
+            }
+            factor = square;
+            owned = 1;
+        }
+    }
+    if (owned) {
+        free(factor.digits);
+    }
+    iod_push_bigint(result);
+}
+
+void iod_word_sqrt(void) { iod_push_float(sqrt(iod_to_float(iod_pop_number("sqrt")))); }
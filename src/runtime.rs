@@ -0,0 +1,62 @@
+/// C runtime linked into compiled programs, see `runtime.c`
+pub const SOURCE: &str = include_str!("runtime.c");
+
+/// Returns the runtime function implementing the builtin word `name`
+pub fn word_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "drop" => "iod_word_drop",
+        "dup" => "iod_word_dup",
+        "swap" => "iod_word_swap",
+        "exit" => "iod_word_exit",
+        "print" => "iod_word_print",
+        "get_line" => "iod_word_get_line",
+        "+" => "iod_word_add",
+        "-" => "iod_word_subtract",
+        "/" => "iod_word_divide",
+        "*" => "iod_word_multiply",
+        "<" => "iod_word_less",
+        ">" => "iod_word_more",
+        "<=" => "iod_word_less_or_equal",
+        ">=" => "iod_word_more_or_equal",
+        "==" => "iod_word_equal",
+        "!=" => "iod_word_not_equal",
+        "and" => "iod_word_and",
+        "or" => "iod_word_or",
+        "xor" => "iod_word_xor",
+        "not" => "iod_word_not",
+        "shl" => "iod_word_shift_left",
+        "shr" => "iod_word_shift_right",
+        "mod" => "iod_word_mod",
+        "divmod" => "iod_word_divmod",
+        "abs" => "iod_word_abs",
+        "neg" => "iod_word_neg",
+        "min" => "iod_word_min",
+        "max" => "iod_word_max",
+        "pow" => "iod_word_pow",
+        "sqrt" => "iod_word_sqrt",
+        "to_int" => "iod_word_to_int",
+        "to_uint" => "iod_word_to_uint",
+        "to_float" => "iod_word_to_float",
+        "to_string" => "iod_word_to_string",
+        "to_bool" => "iod_word_to_bool",
+        "get_int" => "iod_word_get_int",
+        "get_uint" => "iod_word_get_uint",
+        "get_float" => "iod_word_get_float",
+        "read_all" => "iod_word_read_all",
+        "eof?" => "iod_word_eof",
+        "assert" => "iod_word_assert",
+        "assert_eq" => "iod_word_assert_eq",
+        "call" => "iod_word_call",
+        "map" => "iod_word_map",
+        "filter" => "iod_word_filter",
+        "fold" => "iod_word_fold",
+        "each" => "iod_word_each",
+        "times" => "iod_word_times",
+        "read_file" => "iod_word_read_file",
+        "write_file" => "iod_word_write_file",
+        "env" => "iod_word_env",
+        "now" => "iod_word_now",
+        "random" => "iod_word_random",
+        _ => return None,
+    })
+}
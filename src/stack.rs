@@ -15,4 +15,25 @@ impl<T: Clone> Stack<T> {
     pub fn pop(&mut self) -> Option<T> {
         self.vec.pop()
     }
+
+    pub fn top(&self) -> Option<&T> {
+        self.vec.last()
+    }
+
+    pub fn top_mut(&mut self) -> Option<&mut T> {
+        self.vec.last_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Iterates from the bottom of the stack to the top
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.vec.iter()
+    }
 }
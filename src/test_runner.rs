@@ -0,0 +1,306 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    interpreter::Interpreter,
+    lexer::{ILToken, Lexer},
+};
+
+/// Output sink that can still be read after being handed to an `Interpreter`
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A test that did not pass
+struct Failure {
+    location: String,
+    name: String,
+    message: String,
+    output: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Expands directories into the `.iod` files they contain, recursively and
+/// in a stable order. Files are kept as given
+pub fn collect_files(paths: &[String]) -> io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<_>>()?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                walk(&path, files)?;
+            } else if path.extension().is_some_and(|ext| ext == "iod") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            walk(&path, &mut files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Runs the test block at `position` in a fresh interpreter, returns the
+/// error and the line it happened on if the test failed, and what it printed.
+/// Calling `exit` fails the test, it would cut the test short
+fn run_test(source: &str, position: usize) -> (Result<(), (String, usize)>, String) {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::new(
+        Lexer::new(source, false),
+        Some(Box::new(output.clone())),
+        Some(Box::new(io::empty())),
+        false,
+    );
+    interpreter.select_test(position);
+    let result = match interpreter.run() {
+        Err(e) => Err((e.to_string(), interpreter.current_line())),
+        Ok(()) => match interpreter.exit_code() {
+            Some(code) => Err((
+                format!("Test called `exit` with code {}", code),
+                interpreter.exit_line(),
+            )),
+            None => Ok(()),
+        },
+    };
+    (result, output.contents())
+}
+
+/// Runs every test block of `source`, reporting each one to `out`
+fn run_source(
+    file: &str,
+    source: &str,
+    out: &mut dyn Write,
+    summary: &mut Summary,
+    failures: &mut Vec<Failure>,
+) -> io::Result<()> {
+    let mut lexer = Lexer::new(source, false);
+    let (tokens, _) = lexer.tokenize();
+    if let Some((e, span)) = lexer.error() {
+        writeln!(out, "{} ... FAILED", file)?;
+        summary.failed += 1;
+        failures.push(Failure {
+            location: format!("{}:{}", file, span.line),
+            name: String::new(),
+            message: e.summary(),
+            output: String::new(),
+        });
+        return Ok(());
+    }
+
+    let tests = tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(i, token)| match token {
+            ILToken::Test(name, _) => Some((i, name.clone())),
+            _ => None,
+        });
+    for (position, name) in tests {
+        let (result, output) = run_test(source, position);
+        match result {
+            Ok(()) => {
+                writeln!(out, "test {}: {:?} ... ok", file, name)?;
+                summary.passed += 1;
+            }
+            Err((e, line)) => {
+                writeln!(out, "test {}: {:?} ... FAILED", file, name)?;
+                summary.failed += 1;
+                failures.push(Failure {
+                    location: format!("{}:{}", file, line),
+                    name,
+                    message: e,
+                    output,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the test blocks of every file and prints a report, returns how many
+/// tests passed and failed
+pub fn run(files: &[PathBuf], out: &mut dyn Write) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+    let mut failures = vec![];
+
+    for file in files {
+        let name = file.display().to_string();
+        match std::fs::read_to_string(file) {
+            Ok(source) => run_source(&name, &source, out, &mut summary, &mut failures)?,
+            Err(e) => {
+                writeln!(out, "{} ... FAILED", name)?;
+                summary.failed += 1;
+                failures.push(Failure {
+                    location: name,
+                    name: String::new(),
+                    message: e.to_string(),
+                    output: String::new(),
+                });
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        writeln!(out, "\nfailures:")?;
+        for failure in &failures {
+            writeln!(out)?;
+            if failure.name.is_empty() {
+                writeln!(out, "{}", failure.location)?;
+            } else {
+                writeln!(out, "{}: {:?}", failure.location, failure.name)?;
+            }
+            writeln!(out, "    {}", failure.message)?;
+            if !failure.output.is_empty() {
+                writeln!(out, "    output:")?;
+                for line in failure.output.lines() {
+                    writeln!(out, "        {}", line)?;
+                }
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "\ntest result: {}. {} passed; {} failed",
+        if summary.failed == 0 { "ok" } else { "FAILED" },
+        summary.passed,
+        summary.failed
+    )?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(source: &str) -> (String, Summary) {
+        let mut out = vec![];
+        let mut summary = Summary::default();
+        let mut failures = vec![];
+        run_source("t.iod", source, &mut out, &mut summary, &mut failures).unwrap();
+        let failures: Vec<String> = failures
+            .into_iter()
+            .map(|f| format!("{} {} {:?}", f.location, f.message, f.output))
+            .collect();
+        (failures.join("\n"), summary)
+    }
+
+    #[test]
+    fn passing_and_failing_tests() {
+        let src = "\
+fdef square : number dup * fend
+\"not run\" print
+
+test \"squares\"
+    3 square 9 assert_eq
+end
+
+test \"fails\"
+    \"before\" print
+    2 square
+    5 assert_eq
+    \"after\" print
+end";
+
+        let (failures, summary) = report(src);
+        assert_eq!(
+            Summary {
+                passed: 1,
+                failed: 1
+            },
+            summary
+        );
+        assert_eq!("t.iod:11 Assertion failed: 4 != 5 \"before\\n\"", failures);
+    }
+
+    #[test]
+    fn runtime_errors_fail_tests() {
+        let (failures, summary) = report("test \"a\" 1 true assert end test \"b\" oops end");
+
+        assert_eq!(
+            Summary {
+                passed: 1,
+                failed: 1
+            },
+            summary
+        );
+        assert_eq!("t.iod:1 Unknown word: oops \"\"", failures);
+    }
+
+    #[test]
+    fn assert_eq_uses_the_equality_of_eq() {
+        let (failures, summary) = report(
+            "test \"a\" 1 1.0 assert_eq { 1 } { 1 } assert_eq end test \"b\" 1 true assert_eq end",
+        );
+
+        assert_eq!(
+            Summary {
+                passed: 1,
+                failed: 1
+            },
+            summary
+        );
+        assert_eq!(
+            "t.iod:1 `assert_eq` can't compare number and bool \"\"",
+            failures
+        );
+    }
+
+    #[test]
+    fn exit_fails_tests() {
+        let (failures, summary) = report("test \"a\"\n1 exit\nend test \"b\" 0 exit end");
+
+        assert_eq!(
+            Summary {
+                passed: 0,
+                failed: 2
+            },
+            summary
+        );
+        assert_eq!(
+            "t.iod:2 Test called `exit` with code 1 \"\"\n\
+             t.iod:3 Test called `exit` with code 0 \"\"",
+            failures
+        );
+    }
+
+    #[test]
+    fn tests_are_skipped_when_running_the_program() {
+        let src = "1 print test \"t\" 2 print end 3 print";
+
+        assert_eq!("1\n3\n", crate::test_utils::interpret(src, ""));
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+pub use crate::test_runner::SharedBuffer;
+use crate::{
+    interpreter::Interpreter,
+    lexer::{ILToken, Lexer},
+};
+
+/// Runs `src` in the interpreter and returns everything it printed
+pub fn interpret(src: &str, input: &'static str) -> String {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::new(
+        Lexer::new(src, false),
+        Some(Box::new(output.clone())),
+        Some(Box::new(input.as_bytes())),
+        false,
+    );
+    interpreter.run().unwrap();
+    output.contents()
+}
+
+/// Unique path in the temporary directory
+pub fn temp_path(prefix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "iodine-{}-{}-{}",
+        prefix,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+/// Runs a compiled program with `input` on stdin and returns its stdout
+pub fn run_executable(exe: &Path, input: &str) -> String {
+    let mut child = Command::new(exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Compiles a program into the executable at the given path, anything else it
+/// writes has to stay in the same directory
+pub type Build = fn(&[ILToken], &Path);
+
+/// Compiles `src` with `build`, runs it with `input` and returns its stdout
+pub fn compile_and_run(build: Build, src: &str, input: &str) -> String {
+    let dir = temp_path("build");
+    std::fs::create_dir(&dir).unwrap();
+    let exe = dir.join("program");
+    build(&Lexer::new(src, false).parse(), &exe);
+    let output = run_executable(&exe, input);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    output
+}
+
+/// Checks that the program compiled with `build` prints the same as the
+/// interpreter
+pub fn assert_same_output(build: Build, src: &str, input: &'static str) {
+    assert_eq!(interpret(src, input), compile_and_run(build, src, input));
+}
+
+/// Runs `builtin_programs` through `assert_same_output`
+pub fn assert_every_builtin(build: Build) {
+    let path = temp_path("builtin");
+    for (src, input) in builtin_programs(&path) {
+        assert_same_output(build, &src, input);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Program and input using each builtin word, every backend has to print the
+/// same for them. `{file}` stands for a path the program can write to
+pub const BUILTIN_PROGRAMS: &[(&str, &str, &str)] = &[
+    ("drop", "1 2 drop print", ""),
+    ("dup", "3 dup * print \"a\" dup print print", ""),
+    ("swap", "1 \"two\" swap print print", ""),
+    ("exit", "\"before\" print 3 exit \"after\" print", ""),
+    (
+        "print",
+        "\"text\" print 1.5 print -2 print true print { 1 \"a\" { } } print [ 1 ] print \
+         { -18446744073709551616 } print",
+        "",
+    ),
+    (
+        "get_line",
+        "get_line print get_line print",
+        "  first  \nsecond\n",
+    ),
+    (
+        "+",
+        "1 2 + print 0.5 1 + print -9223372036854775808 9223372036854775807 + print \
+         18446744073709551615 1 + print 18446744073709551616 -1 + print \
+         18446744073709551616 0.5 + print",
+        "",
+    ),
+    (
+        "-",
+        "5 7 - print 2.5 1 - print 0 -9223372036854775807 - print \
+         -18446744073709551615 2 - print 18446744073709551616 18446744073709551616 - print",
+        "",
+    ),
+    ("/", "7 2 / print 1 0 / print -1 0 / print 0 0 / print", ""),
+    (
+        "*",
+        "6 7 * print 1.5 2 * print -3 4 * print 0 -1 * print 4294967296 4294967296 * print \
+         18446744073709551615 dup * dup * print -18446744073709551616 0 * print",
+        "",
+    ),
+    (
+        "<",
+        "1 2 < print 2 1 < print -1 18446744073709551615 < print 1.5 1 < print \
+         \"a\" \"b\" < print -18446744073709551616 1 < print \
+         18446744073709551616 18446744073709551617 < print",
+        "",
+    ),
+    (
+        ">",
+        "1 2 > print 2 1 > print 2 1.5 > print \"b\" \"a\" > print false true > print \
+         18446744073709551617 18446744073709551616.0 > print",
+        "",
+    ),
+    (
+        "<=",
+        "1 2 <= print 2 2 <= print 3 2 <= print \"a\" \"a\" <= print",
+        "",
+    ),
+    (
+        ">=",
+        "1 2 >= print 2 2 >= print 3 2.5 >= print \"b\" \"a\" >= print",
+        "",
+    ),
+    (
+        "==",
+        "1 1.0 == print \"a\" \"b\" == print { 1 \"a\" } { 1 \"a\" } == print \
+         -1 18446744073709551615 == print 18446744073709551616 18446744073709551616.0 == print",
+        "",
+    ),
+    (
+        "!=",
+        "1 2 != print \"a\" \"a\" != print { 1 } { 2 } != print",
+        "",
+    ),
+    (
+        "and",
+        "true false and print true true and print 12 10 and print",
+        "",
+    ),
+    (
+        "or",
+        "true false or print false false or print 12 10 or print",
+        "",
+    ),
+    (
+        "xor",
+        "true true xor print true false xor print 12 10 xor print",
+        "",
+    ),
+    ("not", "true not print false not print 5 not print", ""),
+    (
+        "shl",
+        "1 3 shl print 1 63 shl print -1 2 shl print 18446744073709551615 63 shl print",
+        "",
+    ),
+    ("shr", "16 2 shr print -5 1 shr print", ""),
+    ("mod", "7 3 mod print -7 3 mod print 5.5 -2 mod print", ""),
+    (
+        "divmod",
+        "7 2 divmod print print -7 2 divmod print print 1.5 -2 divmod print print",
+        "",
+    ),
+    (
+        "abs",
+        "-5 abs print 5 abs print -2.5 abs print -18446744073709551616 abs print",
+        "",
+    ),
+    (
+        "neg",
+        "5 neg print -5 neg print 2.5 neg print 18446744073709551615 neg print",
+        "",
+    ),
+    (
+        "min",
+        "3 -2 min print 1 2.5 min print -18446744073709551616 1 min print",
+        "",
+    ),
+    (
+        "max",
+        "3 -2 max print 1 2.5 max print 18446744073709551616 1.5 max print",
+        "",
+    ),
+    (
+        "pow",
+        "2 10 pow print -3 3 pow print 2 -1 pow print 2.25 0.5 pow print 2 100 pow print \
+         -3 41 pow print 18446744073709551616 3 pow print",
+        "",
+    ),
+    ("sqrt", "2.25 sqrt print 2 sqrt print", ""),
+    (
+        "to_int",
+        "-2.9 to_int print \"42\" to_int print true to_int print 7 to_int print",
+        "",
+    ),
+    (
+        "to_uint",
+        "2.9 to_uint print \"42\" to_uint print false to_uint print",
+        "",
+    ),
+    (
+        "to_float",
+        "1 to_float print \"2.5\" to_float print true to_float print \
+         18446744073709551617 to_float print",
+        "",
+    ),
+    (
+        "to_string",
+        "{ 1 \"a\" } to_string print 1.0 to_string print true to_string print \
+         -100000000000000000000 to_string print",
+        "",
+    ),
+    (
+        "to_bool",
+        "0 to_bool print 2 to_bool print \"false\" to_bool print \"\" to_bool print \
+         18446744073709551616 to_bool print",
+        "",
+    ),
+    ("get_int", "get_int 1 + print", " -12\n"),
+    ("get_uint", "get_uint 1 + print", "12\n"),
+    ("get_float", "get_float 2 * print", "2.5\n"),
+    ("read_all", "get_line print read_all print", "a\nb\nc\n"),
+    ("eof?", "eof? print get_line drop eof? print", "x\n"),
+    ("assert", "true assert 1 assert \"passed\" print", ""),
+    (
+        "assert_eq",
+        "1 1 assert_eq \"a\" \"a\" assert_eq \"passed\" print",
+        "",
+    ),
+    ("call", "[ 1 2 + ] call print", ""),
+    (
+        "map",
+        "{ 1 2 3 } [ dup * ] map print { } [ 1 ] map print",
+        "",
+    ),
+    ("filter", "{ 1 2 3 4 } [ 2 > ] filter print", ""),
+    (
+        "fold",
+        "{ 1 2 3 } 0 [ + ] fold print { } \"empty\" [ + ] fold print",
+        "",
+    ),
+    ("each", "{ 1 \"a\" } [ print ] each", ""),
+    (
+        "times",
+        "3 [ \"hi\" print ] times 0 [ \"never\" print ] times",
+        "",
+    ),
+    (
+        "write_file",
+        "\"written\" \"{file}\" write_file \"{file}\" read_file print",
+        "",
+    ),
+    ("read_file", "\"Cargo.toml\" read_file \"\" != print", ""),
+    (
+        "env",
+        "\"IODINE_UNSET_VARIABLE\" env print \"PATH\" env \"\" != print",
+        "",
+    ),
+    ("now", "now 1000000000 > print", ""),
+    ("random", "random dup 0 >= swap 1 < and print", ""),
+];
+
+/// `BUILTIN_PROGRAMS` merged into as few programs as possible to keep the
+/// number of compilations down. Every part prints the word it tests first, the
+/// ones reading input or exiting stay on their own
+pub fn builtin_programs(file: &Path) -> Vec<(String, &'static str)> {
+    let mut merged = String::new();
+    let mut programs = vec![];
+    for (word, src, input) in BUILTIN_PROGRAMS {
+        let src = format!(
+            "{:?} print {}\n",
+            word,
+            src.replace("{file}", &file.to_string_lossy())
+        );
+        if input.is_empty() && *word != "exit" {
+            merged.push_str(&src);
+        } else {
+            programs.push((src, *input));
+        }
+    }
+    programs.push((merged, ""));
+    programs
+}
+
+#[test]
+fn every_builtin_has_a_program() {
+    for builtin in crate::built_in_words::BUILTINS {
+        assert!(
+            BUILTIN_PROGRAMS
+                .iter()
+                .any(|(word, ..)| *word == builtin.name),
+            "`{}` has no program",
+            builtin.name
+        );
+    }
+}
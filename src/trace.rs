@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::{
+    disasm,
+    interpreter::{self, StackValue},
+    lexer::ILToken,
+    stack::Stack,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFormat {
+    /// One human readable line per executed token
+    Text,
+    /// One JSON object per executed token
+    Json,
+}
+
+/// Logs every executed token together with the stack after it ran
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+}
+
+fn value_to_json(value: &StackValue) -> Value {
+    let inner = match value {
+        StackValue::String(str) => json!(str),
+        StackValue::UnsignedInt(num) => json!(num),
+        StackValue::SignedInt(num) => json!(num),
+        // NOTE: Kept exact as a string, JSON readers often parse numbers as floats
+        StackValue::BigInt(num) => json!(num.to_string()),
+        // NOTE: JSON has no representation for NaN and infinities
+        StackValue::Float(num) if !num.is_finite() => json!(num.to_string()),
+        StackValue::Float(num) => json!(num),
+        StackValue::Bool(bool) => json!(bool),
+        StackValue::List(items) => Value::Array(items.iter().map(value_to_json).collect()),
+        StackValue::Quotation(quotation) => json!(quotation.start),
+    };
+    json!({ "type": value.type_name(), "value": inner })
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, format: TraceFormat) -> Self {
+        Self { output, format }
+    }
+
+    pub fn record(
+        &mut self,
+        position: usize,
+        line: usize,
+        token: &ILToken,
+        stack: &Stack<StackValue>,
+    ) {
+        match self.format {
+            TraceFormat::Text => writeln!(
+                self.output,
+                "{:04}  line {}  {}  {}",
+                position,
+                line,
+                disasm::describe(token),
+                interpreter::format_stack(stack)
+            )
+            .unwrap(),
+            TraceFormat::Json => {
+                let stack: Vec<Value> = stack.iter().map(value_to_json).collect();
+                let entry = json!({
+                    "position": position,
+                    "line": line,
+                    "token": disasm::describe(token),
+                    "stack": stack,
+                });
+                writeln!(self.output, "{}", entry).unwrap();
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, lexer::Lexer, test_utils::SharedBuffer};
+
+    fn trace(src: &str, format: TraceFormat) -> String {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::new(Lexer::new(src, false), None, None, false);
+        interpreter.set_tracer(Tracer::new(Box::new(output.clone()), format));
+        interpreter.run().unwrap();
+        output.contents()
+    }
+
+    #[test]
+    fn text() {
+        let expected = "\
+0000  line 1  push_string \"a\"  [\"a\"]
+0001  line 2  push_float 1.5  [\"a\", 1.5]
+0002  line 2  word drop  [\"a\"]
+";
+
+        assert_eq!(expected, trace("\"a\"\n1.5 drop", TraceFormat::Text));
+    }
+
+    #[test]
+    fn json_lines() {
+        let expected = concat!(
+            r#"{"line":1,"position":0,"stack":[{"type":"uint","value":2}],"token":"push_uint 2"}"#,
+            "\n",
+            r#"{"line":1,"position":1,"stack":[{"type":"uint","value":2},{"type":"uint","value":2}],"token":"word dup"}"#,
+            "\n",
+            r#"{"line":2,"position":2,"stack":[{"type":"uint","value":4}],"token":"word +"}"#,
+            "\n",
+        );
+
+        assert_eq!(expected, trace("2 dup\n+", TraceFormat::Json));
+    }
+
+    #[test]
+    fn flushed_after_failing_run() {
+        let output = SharedBuffer::default();
+        let buffered = std::io::BufWriter::new(output.clone());
+        let mut interpreter =
+            Interpreter::new(Lexer::new("1 frobnicate", false), None, None, false);
+        interpreter.set_tracer(Tracer::new(Box::new(buffered), TraceFormat::Text));
+        assert!(interpreter.run().is_err());
+        assert_eq!("", output.contents());
+
+        interpreter.take_tracer().unwrap().flush().unwrap();
+        assert_eq!("0000  line 1  push_uint 1  [1]\n", output.contents());
+    }
+}